@@ -0,0 +1,15 @@
+use solana_rpc_tower::prelude::*;
+
+#[tokio::main]
+async fn main() {
+    // Requests go to the first URL, and fall over to the next one on transport errors,
+    // 5xx and 429 responses, or "node is unhealthy" errors.
+    let client = RpcClientBuilder::new()
+        .failover(vec![
+            Url::try_from("https://api.mainnet-beta.solana.com").unwrap(),
+            Url::try_from("https://solana-rpc.publicnode.com").unwrap(),
+        ])
+        .on_served(|method, url| println!("{method} served by {url}"))
+        .build_rpc_client();
+    let _ = client.get_version().await;
+}
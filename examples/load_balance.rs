@@ -0,0 +1,16 @@
+use solana_rpc_tower::prelude::*;
+
+#[tokio::main]
+async fn main() {
+    // Requests are spread over both URLs, preferring whichever currently has the lower latency.
+    // Also see `BalanceStrategy::RoundRobin`, `BalanceStrategy::Weighted`
+    // and `BalanceStrategy::LeastOutstanding`.
+    let client = RpcClientBuilder::new()
+        .balance(vec![
+            Url::try_from("https://api.mainnet-beta.solana.com").unwrap(),
+            Url::try_from("https://solana-rpc.publicnode.com").unwrap(),
+        ])
+        .strategy(BalanceStrategy::peak_ewma())
+        .build_rpc_client();
+    let _ = client.get_version().await;
+}
@@ -0,0 +1,18 @@
+use solana_rpc_tower::{prelude::*, service::rpc_sender_impl::http_service};
+
+#[tokio::main]
+async fn main() {
+    let default_url = Url::try_from("https://api.mainnet-beta.solana.com").unwrap();
+    let staked_url = Url::try_from("https://staked.example.com").unwrap();
+    let heavy_url = Url::try_from("https://gpa.example.com").unwrap();
+    // Transactions go to a staked node, without 429 retries so that they can be resent
+    // with a fresh blockhash instead. `getProgramAccounts` goes to a node that can handle it,
+    // and everything else goes to the default node.
+    let client = RpcClientBuilder::new()
+        .router(http_service(default_url.clone(), 5))
+        .route([RpcRequest::SendTransaction], http_service(staked_url, 0))
+        .route([RpcRequest::GetProgramAccounts], http_service(heavy_url, 5))
+        .url(default_url.to_string())
+        .build_rpc_client();
+    let _ = client.get_version().await;
+}
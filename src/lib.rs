@@ -4,16 +4,35 @@
 //! including rate limiting, request filtering, retry logic, and more.
 pub mod middleware;
 pub mod service;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 
 pub mod prelude {
-    pub use crate::middleware::{MaybeEarlyReturnLayer, TooManyRequestsRetry};
+    pub use crate::middleware::{
+        CachePolicy, CircuitBreakerConfig, CircuitBreakerLayer, CoalesceLayer,
+        MaybeEarlyReturnLayer, MetricsLayer, ResponseCacheLayer, RpcErrorRetry,
+        TooManyRequestsRetry, TraceLayer,
+    };
     pub use crate::service::{
-        builder::{FnClientBuilder, HttpClientBuilder, ServiceBuilderExt},
+        builder::{
+            BalanceClientBuilder, FailoverClientBuilder, FnClientBuilder, HedgeClientBuilder,
+            HttpClientBuilder, QuorumClientBuilder, RouterClientBuilder, ServiceBuilderExt,
+            SlotAwareClientBuilder,
+        },
         parse_response_body::ParseResponseBodyLayer,
         rpc_sender_impl::{RpcClientSender, SolanaClientRequest, SolanaClientResponse},
-        HttpRequestLayer,
+        BalanceService, BalanceStrategy, FailoverService, HedgeService, HttpRequestLayer,
+        QuorumService, Redactor, RouterService, SlotAwareService,
     };
     pub use crate::service::{RpcRequest, Value};
+    #[cfg(feature = "test-utils")]
+    pub use crate::{
+        middleware::{FaultInjectionLayer, RecordLayer},
+        service::{
+            builder::{MockClientBuilder, ReplayClientBuilder},
+            FakeLedger, MockService, ReplayService,
+        },
+    };
     pub use reqwest::Url;
     pub use solana_client::client_error::ClientError;
     pub use tower::{BoxError, ServiceBuilder as RpcClientBuilder};
@@ -1,6 +1,25 @@
 pub mod cache;
+pub mod circuit_breaker;
+pub mod coalesce;
 pub mod early_return;
+#[cfg(feature = "test-utils")]
+pub mod fault;
+pub mod metrics;
+#[cfg(feature = "test-utils")]
+pub mod record;
 pub mod retry_429;
+pub mod retry_rpc_error;
+pub mod trace;
 
+pub use cache::{CacheCapacity, CachePolicy, CacheStatus, Eviction, ResponseCacheLayer};
+pub use circuit_breaker::{CircuitBreakerConfig, CircuitBreakerLayer};
+pub use coalesce::CoalesceLayer;
 pub use early_return::MaybeEarlyReturnLayer;
-pub use retry_429::TooManyRequestsRetry;
+#[cfg(feature = "test-utils")]
+pub use fault::{Fault, FaultInjectionLayer, Latency};
+pub use metrics::{render_prometheus, MetricsLayer};
+#[cfg(feature = "test-utils")]
+pub use record::RecordLayer;
+pub use retry_429::{Jitter, TooManyRequestsRetry};
+pub use retry_rpc_error::RpcErrorRetry;
+pub use trace::{BodyLogging, TraceLayer};
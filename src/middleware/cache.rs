@@ -1,8 +1,10 @@
+mod bounded;
+
 use std::{
     collections::HashMap,
     future::Future,
     pin::Pin,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     task::{Context, Poll},
     time::{Duration, Instant},
 };
@@ -11,34 +13,321 @@ use futures::{
     future::{ready, BoxFuture},
     FutureExt,
 };
-use serde_json::Value;
+use serde_json::{json, Value};
 use solana_client::rpc_request::RpcRequest;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 use tower::{BoxError, Layer, Service};
 
-use crate::service::rpc_sender_impl::SolanaClientRequest;
+use crate::service::{rpc_sender_impl::SolanaClientRequest, stats_updater::StatsUpdater};
+
+pub use bounded::{approximate_size, BoundedMap, CacheCapacity, Eviction};
+
+/// What a [ResponseCacheService] caches a request by: its params without the commitment
+/// and `minContextSlot`, and its commitment, or else the default commitment.
+/// So e.g. `[pubkey]` and `[pubkey, {"commitment": "finalized"}]` are the same request
+/// when the default commitment is finalized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheKey {
+    pub params: Value,
+    pub commitment: CommitmentLevel,
+    /// Cached responses from before this slot aren't served.
+    pub min_context_slot: Option<u64>,
+}
+
+impl CacheKey {
+    pub fn new(params: &Value, default_commitment: CommitmentLevel) -> Self {
+        let mut params = params.clone();
+        let mut commitment = default_commitment;
+        let mut min_context_slot = None;
+        if let Value::Array(values) = &mut params {
+            for config in values.iter_mut().filter_map(Value::as_object_mut) {
+                let level = config
+                    .get("commitment")
+                    .and_then(|level| serde_json::from_value(level.clone()).ok());
+                if let Some(level) = level {
+                    commitment = level;
+                    config.remove("commitment");
+                }
+                if let Some(slot) = config.remove("minContextSlot") {
+                    min_context_slot = slot.as_u64();
+                }
+            }
+            // Configs are optional, so an empty one is the same as none
+            while values
+                .last()
+                .is_some_and(|v| v.is_null() || v.as_object().is_some_and(|o| o.is_empty()))
+            {
+                values.pop();
+            }
+        }
+        Self {
+            params,
+            commitment,
+            min_context_slot,
+        }
+    }
+
+    /// The key of the same request to `method` at `commitment`, in the cached values.
+    fn at(&self, method: RpcRequest, commitment: CommitmentLevel) -> Value {
+        json!([method.to_string(), commitment, self.params])
+    }
+}
 
+/// A cached response, and when it was received.
 #[derive(Debug, Clone)]
 pub struct CacheEntry {
     response: Value,
     at: Instant,
+    /// The `context.slot` of the response, if it has one.
+    slot: Option<u64>,
+    /// The latest slot at the commitment of the response when it was received.
+    latest_slot: u64,
+    /// Whether a request to replace the response is in flight.
+    refreshing: bool,
+}
+
+type CachedValues = Arc<RwLock<BoundedMap<Value, CacheEntry>>>;
+
+type Counters = Arc<Mutex<HashMap<RpcRequest, CacheCounters>>>;
+
+/// How long a [ResponseCacheService] keeps the responses of a method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    Never,
+    /// For this long after the response.
+    Ttl(Duration),
+    /// Until a response at the same commitment comes through with a newer slot, either in its
+    /// `context.slot` or as the result of `GetSlot`, and for at most this long, since cache hits
+    /// don't tell whether the chain has moved on.
+    UntilSlotAdvances(Duration),
+}
+
+impl CachePolicy {
+    fn max_cache_age(self) -> Option<Duration> {
+        match self {
+            Self::Never => None,
+            Self::Ttl(max_cache_age) | Self::UntilSlotAdvances(max_cache_age) => {
+                Some(max_cache_age)
+            }
+        }
+    }
+}
+
+/// Cache hits and misses of a method.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheCounters {
+    pub hits: usize,
+    pub misses: usize,
 }
 
+tokio::task_local! {
+    static CACHE_STATUS: Arc<Mutex<Option<CacheStatus>>>;
+}
+
+/// How a response served by a [ResponseCacheService] was cached, see [CacheStatus::scope].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStatus {
+    /// How long ago the response was received.
+    pub age: Duration,
+    /// Whether the response had expired, and was served while being refreshed,
+    /// or because refreshing it failed.
+    pub stale: bool,
+}
+
+impl CacheStatus {
+    /// Run `fut`, e.g. a request of an `RpcClient`, and tell how the last response it got
+    /// was cached, or `None` if it wasn't served from a cache.
+    /// This only works for requests made within the task that awaits `fut`.
+    pub async fn scope<F: Future>(fut: F) -> (F::Output, Option<Self>) {
+        let status = Arc::new(Mutex::new(None));
+        let output = CACHE_STATUS.scope(status.clone(), fut).await;
+        let status = *status.lock().unwrap();
+        (output, status)
+    }
+
+    fn set(status: Option<Self>) {
+        let _ = CACHE_STATUS.try_with(|current| *current.lock().unwrap() = status);
+    }
+
+    fn of(entry: &CacheEntry, stale: bool) -> Option<Self> {
+        let age = entry.at.elapsed();
+        Some(Self { age, stale })
+    }
+}
+
+/// The latest slot seen in responses, by commitment.
+#[derive(Debug, Clone, Default)]
+struct LatestSlots(Arc<Mutex<HashMap<CommitmentLevel, u64>>>);
+
+impl LatestSlots {
+    fn get(&self, commitment: CommitmentLevel) -> u64 {
+        let slots = self.0.lock().unwrap();
+        slots.get(&commitment).copied().unwrap_or_default()
+    }
+
+    /// Learn the latest slot from a response at `commitment`, which weaker commitments
+    /// have reached too. Returns the latest slot at `commitment`.
+    fn observe(&self, method: RpcRequest, commitment: CommitmentLevel, response: &Value) -> u64 {
+        let slot = match method {
+            RpcRequest::GetSlot => response.as_u64(),
+            _ => response.pointer("/context/slot").and_then(Value::as_u64),
+        };
+        let mut slots = self.0.lock().unwrap();
+        if let Some(slot) = slot {
+            for commitment in weaker_or_same(commitment) {
+                let latest = slots.entry(commitment).or_default();
+                *latest = (*latest).max(slot);
+            }
+        }
+        slots.get(&commitment).copied().unwrap_or_default()
+    }
+}
+
+/// The commitments up to and including `commitment`, from the weakest.
+fn weaker_or_same(commitment: CommitmentLevel) -> impl Iterator<Item = CommitmentLevel> {
+    let weaker = [
+        CommitmentLevel::Processed,
+        CommitmentLevel::Confirmed,
+        CommitmentLevel::Finalized,
+    ]
+    .into_iter()
+    .take_while(move |&weaker| weaker != commitment);
+    weaker.chain([commitment])
+}
+
+/// Caches the successful responses of methods, each for as long as its [CachePolicy] says,
+/// in one store. Methods without a policy aren't cached.
+///
+/// After that, responses can still be served for a while, see
+/// [ResponseCacheService::stale_while_revalidate] and [ResponseCacheService::stale_if_error].
+/// Stale responses are counted in the [RequestStats](crate::service::stats_updater::RequestStats)
+/// of the method, and each response tells whether it was stale in its [CacheStatus].
+///
+/// Requests are cached by their [CacheKey], so they share responses regardless of
+/// whether they spell out the default commitment, and a response is only served to requests
+/// with a `minContextSlot` if it is at least that recent.
 #[derive(Debug, Clone)]
 pub struct ResponseCacheService<S> {
     inner: S,
-    request_type: RpcRequest,
-    max_cache_age: Duration,
-    cached_values: Arc<RwLock<HashMap<Value, CacheEntry>>>,
+    policies: Arc<HashMap<RpcRequest, CachePolicy>>,
+    max_cache_age_by_commitment: HashMap<CommitmentLevel, Duration>,
+    default_commitment: CommitmentLevel,
+    invalidate_on_newer_slot: bool,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+    cached_values: CachedValues,
+    latest_slots: LatestSlots,
+    counters: Counters,
 }
 
 impl<S> ResponseCacheService<S> {
     pub fn new(inner: S, request_type: RpcRequest, max_cache_age: Duration) -> Self {
+        Self::with_capacity(inner, request_type, max_cache_age, CacheCapacity::default())
+    }
+
+    pub fn with_capacity(
+        inner: S,
+        request_type: RpcRequest,
+        max_cache_age: Duration,
+        capacity: CacheCapacity,
+    ) -> Self {
+        let policies = HashMap::from([(request_type, CachePolicy::Ttl(max_cache_age))]);
         Self {
             inner,
-            request_type,
-            max_cache_age,
-            cached_values: Arc::new(RwLock::new(HashMap::new())),
+            policies: Arc::new(policies),
+            max_cache_age_by_commitment: HashMap::new(),
+            default_commitment: CommitmentLevel::Finalized,
+            invalidate_on_newer_slot: false,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+            cached_values: Arc::new(RwLock::new(BoundedMap::new(capacity))),
+            latest_slots: LatestSlots::default(),
+            counters: Counters::default(),
+        }
+    }
+
+    /// Cache the responses of `method` as `policy` says, instead of how it was cached before.
+    pub fn policy(mut self, method: RpcRequest, policy: CachePolicy) -> Self {
+        Arc::make_mut(&mut self.policies).insert(method, policy);
+        self
+    }
+
+    /// The commitment of requests that don't have one, i.e. that of the client. Finalized by default.
+    pub fn default_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.default_commitment = commitment.commitment;
+        self
+    }
+
+    /// Cache responses at `commitment` for `max_cache_age` instead, whatever the method,
+    /// e.g. `processed` ones for less time, since they go out of date sooner.
+    pub fn max_cache_age_for(
+        mut self,
+        commitment: CommitmentLevel,
+        max_cache_age: Duration,
+    ) -> Self {
+        self.max_cache_age_by_commitment
+            .insert(commitment, max_cache_age);
+        self
+    }
+
+    /// Drop cached responses to a request once a response to it comes with a newer
+    /// `context.slot`, at its commitment or a weaker one, and never replace a response with
+    /// an older one. E.g. a `processed` response doesn't drop a `finalized` one, which is
+    /// expected to be behind, but a `finalized` one drops older `processed` ones.
+    pub fn invalidate_on_newer_slot(mut self, invalidate: bool) -> Self {
+        self.invalidate_on_newer_slot = invalidate;
+        self
+    }
+
+    /// For this long after a response expires, keep serving it right away,
+    /// while a request in a background task replaces it. Needs a tokio runtime.
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = window;
+        self
+    }
+
+    /// For this long after a response expires, serve it when the request to replace it fails.
+    pub fn stale_if_error(mut self, window: Duration) -> Self {
+        self.stale_if_error = window;
+        self
+    }
+
+    /// How many responses are cached, including expired ones that weren't dropped yet.
+    pub fn cached_responses(&self) -> usize {
+        self.cached_values.read().unwrap().len()
+    }
+
+    /// Drop expired responses now, e.g. from a periodic task,
+    /// instead of waiting for them to be looked up or pushed out.
+    pub fn purge_expired(&self) -> usize {
+        self.cached_values.write().unwrap().purge_expired()
+    }
+
+    /// Cache hits and misses, by method.
+    pub fn counters(&self) -> HashMap<RpcRequest, CacheCounters> {
+        self.counters.lock().unwrap().clone()
+    }
+
+    fn max_cache_age(&self, policy: CachePolicy, commitment: CommitmentLevel) -> Option<Duration> {
+        let max_cache_age = policy.max_cache_age()?;
+        let by_commitment = self.max_cache_age_by_commitment.get(&commitment);
+        Some(by_commitment.copied().unwrap_or(max_cache_age))
+    }
+
+    /// Whether any method is cached until the slot advances, so that slots have to be tracked.
+    fn tracks_slots(&self) -> bool {
+        let mut policies = self.policies.values();
+        policies.any(|policy| matches!(policy, CachePolicy::UntilSlotAdvances(_)))
+    }
+
+    fn count(&self, method: RpcRequest, hit: bool) {
+        let mut counters = self.counters.lock().unwrap();
+        let counters = counters.entry(method).or_default();
+        match hit {
+            true => counters.hits += 1,
+            false => counters.misses += 1,
         }
+        StatsUpdater::with_current(|stats| stats.add_cache_lookup(hit));
     }
 }
 
@@ -57,41 +346,275 @@ where
     }
 
     fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
-        if req.0 == self.request_type {
-            if let Some(entry) = self.cached_values.read().unwrap().get(&req.1) {
-                if entry.at.elapsed() < self.max_cache_age {
-                    return Box::pin(ready(Ok(entry.response.clone())));
-                }
+        let method = req.0;
+        let policy = self.policies.get(&method).copied();
+        let policy = policy.unwrap_or(CachePolicy::Never);
+        let key = CacheKey::new(&req.1, self.default_commitment);
+        let Some(max_cache_age) = self.max_cache_age(policy, key.commitment) else {
+            if !self.tracks_slots() {
+                return Box::pin(self.inner.call(req));
             }
-            return Box::pin(CachedResponseFuture {
-                inner_fut: Box::pin(self.inner.call(req.clone())),
-                request: req,
-                cached_values: self.cached_values.clone(),
+            // The responses of uncached methods can still tell that the slot advanced
+            let fut = self.inner.call(req);
+            let latest_slots = self.latest_slots.clone();
+            return Box::pin(async move {
+                let response = fut.await?;
+                latest_slots.observe(method, key.commitment, &response);
+                Ok(response)
             });
+        };
+        let keep_for = max_cache_age + self.stale_while_revalidate.max(self.stale_if_error);
+        let store = Store {
+            method,
+            key,
+            keep_for,
+            invalidate: self.invalidate_on_newer_slot,
+            cached_values: self.cached_values.clone(),
+            latest_slots: self.latest_slots.clone(),
+        };
+        let latest_slot = self.latest_slots.get(store.key.commitment);
+        let mut cached_values = self.cached_values.write().unwrap();
+        let mut stale = None;
+        let entry = cached_values
+            .get_mut(&store.key.at(method, store.key.commitment))
+            .filter(|entry| {
+                let min_context_slot = store.key.min_context_slot;
+                min_context_slot.is_none_or(|min| entry.slot.is_some_and(|slot| slot >= min))
+            });
+        if let Some(entry) = entry {
+            let mut age = entry.at.elapsed();
+            if matches!(policy, CachePolicy::UntilSlotAdvances(_))
+                && latest_slot > entry.latest_slot
+            {
+                // Expired when the slot advanced, which is as late as it could have been
+                age = age.max(max_cache_age);
+            }
+            if age < max_cache_age {
+                self.count(method, true);
+                CacheStatus::set(CacheStatus::of(entry, false));
+                return Box::pin(ready(Ok(entry.response.clone())));
+            }
+            // Refreshing in the background needs a runtime to spawn on
+            let revalidate = age < max_cache_age + self.stale_while_revalidate;
+            let runtime = tokio::runtime::Handle::try_current().ok();
+            if let Some(runtime) = runtime.filter(|_| revalidate) {
+                if !entry.refreshing {
+                    entry.refreshing = true;
+                    let fut = self.inner.call(req.clone());
+                    runtime.spawn(refresh(fut, store));
+                }
+                self.count(method, true);
+                StatsUpdater::with_current(|stats| stats.add_stale_response());
+                CacheStatus::set(CacheStatus::of(entry, true));
+                return Box::pin(ready(Ok(entry.response.clone())));
+            }
+            if age < max_cache_age + self.stale_if_error {
+                stale = Some(entry.clone());
+            }
+        }
+        drop(cached_values);
+        self.count(method, false);
+        CacheStatus::set(None);
+        let fut = CachedResponseFuture {
+            inner_fut: Box::pin(self.inner.call(req.clone())),
+            request: req,
+            store,
+            stale,
+        };
+        Box::pin(fut)
+    }
+}
+
+/// Where and how to cache a response.
+#[derive(Debug, Clone)]
+struct Store {
+    method: RpcRequest,
+    key: CacheKey,
+    keep_for: Duration,
+    invalidate: bool,
+    cached_values: CachedValues,
+    latest_slots: LatestSlots,
+}
+
+impl Store {
+    fn response(&self, response: Value) {
+        let commitment = self.key.commitment;
+        let latest_slot = self
+            .latest_slots
+            .observe(self.method, commitment, &response);
+        let mut cached_values = self.cached_values.write().unwrap();
+        let key = self.key.at(self.method, commitment);
+        let slot = response.pointer("/context/slot").and_then(Value::as_u64);
+        if let Some(slot) = slot.filter(|_| self.invalidate) {
+            if cached_slot(&cached_values, &key).is_some_and(|cached| cached > slot) {
+                // Keep the newer response, and let it be refreshed again
+                if let Some(entry) = cached_values.peek_mut(&key) {
+                    entry.refreshing = false;
+                }
+                return;
+            }
+            for commitment in weaker_or_same(commitment) {
+                let key = self.key.at(self.method, commitment);
+                if cached_slot(&cached_values, &key).is_some_and(|cached| cached < slot) {
+                    cached_values.remove(&key);
+                }
+            }
+        }
+        let size = approximate_size(&key) + approximate_size(&response);
+        let entry = CacheEntry {
+            response,
+            at: Instant::now(),
+            slot,
+            latest_slot,
+            refreshing: false,
+        };
+        cached_values.insert(key, entry, size, Instant::now() + self.keep_for);
+    }
+}
+
+fn cached_slot(cached_values: &BoundedMap<Value, CacheEntry>, key: &Value) -> Option<u64> {
+    cached_values.peek(key)?.slot
+}
+
+/// Replace a stale response in the background.
+async fn refresh<F>(fut: F, store: Store)
+where
+    F: Future<Output = Result<Value, BoxError>>,
+{
+    match fut.await {
+        Ok(response) => store.response(response),
+        Err(e) => {
+            let params = &store.key.params;
+            tracing::warn!(err = %e, %params, "failed to refresh stale response");
+            let key = store.key.at(store.method, store.key.commitment);
+            if let Some(entry) = store.cached_values.write().unwrap().get_mut(&key) {
+                entry.refreshing = false;
+            }
         }
-        Box::pin(self.inner.call(req))
     }
 }
 
+/// Builds [ResponseCacheService]s that share one store, so that the layer can be cloned
+/// into several clients and still tell how the cache is doing.
+#[derive(Debug, Clone)]
 pub struct ResponseCacheLayer {
-    request_type: RpcRequest,
-    max_cache_age: Duration,
+    policies: HashMap<RpcRequest, CachePolicy>,
+    max_cache_age_by_commitment: HashMap<CommitmentLevel, Duration>,
+    default_commitment: CommitmentConfig,
+    invalidate_on_newer_slot: bool,
+    stale_while_revalidate: Duration,
+    stale_if_error: Duration,
+    cached_values: CachedValues,
+    latest_slots: LatestSlots,
+    counters: Counters,
 }
 
-impl ResponseCacheLayer {
-    pub fn new(request_type: RpcRequest, max_cache_age: Duration) -> Self {
+impl Default for ResponseCacheLayer {
+    /// A layer that caches nothing until it's given a [ResponseCacheLayer::policy].
+    fn default() -> Self {
         Self {
-            request_type,
-            max_cache_age,
+            policies: HashMap::new(),
+            max_cache_age_by_commitment: HashMap::new(),
+            default_commitment: CommitmentConfig::finalized(),
+            invalidate_on_newer_slot: false,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+            cached_values: Arc::new(RwLock::new(BoundedMap::new(CacheCapacity::default()))),
+            latest_slots: LatestSlots::default(),
+            counters: Counters::default(),
         }
     }
 }
 
+impl ResponseCacheLayer {
+    pub fn new(request_type: RpcRequest, max_cache_age: Duration) -> Self {
+        Self::default().policy(request_type, CachePolicy::Ttl(max_cache_age))
+    }
+
+    /// See [ResponseCacheService::policy].
+    pub fn policy(mut self, method: RpcRequest, policy: CachePolicy) -> Self {
+        self.policies.insert(method, policy);
+        self
+    }
+
+    /// See [ResponseCacheService::default_commitment].
+    pub fn default_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.default_commitment = commitment;
+        self
+    }
+
+    /// See [ResponseCacheService::max_cache_age_for].
+    pub fn max_cache_age_for(
+        mut self,
+        commitment: CommitmentLevel,
+        max_cache_age: Duration,
+    ) -> Self {
+        self.max_cache_age_by_commitment
+            .insert(commitment, max_cache_age);
+        self
+    }
+
+    /// See [ResponseCacheService::invalidate_on_newer_slot].
+    pub fn invalidate_on_newer_slot(mut self, invalidate: bool) -> Self {
+        self.invalidate_on_newer_slot = invalidate;
+        self
+    }
+
+    /// Bound the cache, which is unbounded by default. Drops all cached responses.
+    pub fn capacity(mut self, capacity: CacheCapacity) -> Self {
+        self.cached_values = Arc::new(RwLock::new(BoundedMap::new(capacity)));
+        self
+    }
+
+    /// See [ResponseCacheService::stale_while_revalidate].
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = window;
+        self
+    }
+
+    /// See [ResponseCacheService::stale_if_error].
+    pub fn stale_if_error(mut self, window: Duration) -> Self {
+        self.stale_if_error = window;
+        self
+    }
+
+    /// See [ResponseCacheService::counters].
+    pub fn counters(&self) -> HashMap<RpcRequest, CacheCounters> {
+        self.counters.lock().unwrap().clone()
+    }
+
+    /// See [ResponseCacheService::cached_responses].
+    pub fn cached_responses(&self) -> usize {
+        self.cached_values.read().unwrap().len()
+    }
+
+    /// See [ResponseCacheService::purge_expired].
+    pub fn purge_expired(&self) -> usize {
+        self.cached_values.write().unwrap().purge_expired()
+    }
+
+    /// Drop all cached responses.
+    pub fn clear(&self) {
+        self.cached_values.write().unwrap().clear();
+    }
+}
+
 impl<S> Layer<S> for ResponseCacheLayer {
     type Service = ResponseCacheService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        ResponseCacheService::new(inner, self.request_type, self.max_cache_age)
+        ResponseCacheService {
+            inner,
+            policies: Arc::new(self.policies.clone()),
+            max_cache_age_by_commitment: self.max_cache_age_by_commitment.clone(),
+            default_commitment: self.default_commitment.commitment,
+            invalidate_on_newer_slot: self.invalidate_on_newer_slot,
+            stale_while_revalidate: self.stale_while_revalidate,
+            stale_if_error: self.stale_if_error,
+            cached_values: self.cached_values.clone(),
+            latest_slots: self.latest_slots.clone(),
+            counters: self.counters.clone(),
+        }
     }
 }
 
@@ -99,21 +622,45 @@ pub struct CachedResponseFuture<F> {
     // The response body is awaited and parsed as JSON-RPC output after this
     inner_fut: Pin<Box<F>>,
     request: SolanaClientRequest,
-    cached_values: Arc<RwLock<HashMap<Value, CacheEntry>>>,
+    store: Store,
+    stale: Option<CacheEntry>,
 }
 
 impl<F> CachedResponseFuture<F> {
+    /// Caches the response of `fut` by `key`, for `keep_for`.
     pub fn new(
         fut: F,
         request: SolanaClientRequest,
-        cached_values: Arc<RwLock<HashMap<Value, CacheEntry>>>,
+        key: CacheKey,
+        keep_for: Duration,
+        cached_values: CachedValues,
     ) -> Self {
         Self {
             inner_fut: Box::pin(fut),
+            store: Store {
+                method: request.0,
+                key,
+                keep_for,
+                invalidate: false,
+                cached_values,
+                latest_slots: LatestSlots::default(),
+            },
             request,
-            cached_values,
+            stale: None,
         }
     }
+
+    /// See [ResponseCacheService::invalidate_on_newer_slot].
+    pub fn invalidate_on_newer_slot(mut self, invalidate: bool) -> Self {
+        self.store.invalidate = invalidate;
+        self
+    }
+
+    /// Resolve to the response of this entry instead of failing, if any.
+    pub fn stale_if_error(mut self, stale: Option<CacheEntry>) -> Self {
+        self.stale = stale;
+        self
+    }
 }
 
 impl<F> Future for CachedResponseFuture<F>
@@ -127,17 +674,18 @@ where
             Poll::Pending => Poll::Pending,
             Poll::Ready(r) => match r {
                 Ok(r) => {
-                    let entry = CacheEntry {
-                        response: r.clone(),
-                        at: Instant::now(),
-                    };
-                    self.cached_values
-                        .write()
-                        .unwrap()
-                        .insert(self.request.1.clone(), entry);
+                    self.store.response(r.clone());
                     Poll::Ready(Ok(r))
                 }
-                Err(e) => Poll::Ready(Err(e.into())),
+                Err(e) => match self.stale.take() {
+                    Some(stale) => {
+                        tracing::warn!(err = %e, method = %self.request.0, "serving stale response");
+                        StatsUpdater::with_current(|stats| stats.add_stale_response());
+                        CacheStatus::set(CacheStatus::of(&stale, true));
+                        Poll::Ready(Ok(stale.response))
+                    }
+                    None => Poll::Ready(Err(e)),
+                },
             },
         }
     }
@@ -0,0 +1,308 @@
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+/// How often inserting into a [BoundedMap] also drops all expired entries.
+/// In between, expired entries are only dropped when they are looked up.
+const PURGE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which entry makes room when a [BoundedMap] is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// The least recently used entry is evicted.
+    #[default]
+    Lru,
+    /// The least recently used entry is evicted, but only if the new entry was looked up
+    /// more often lately, otherwise the new entry isn't cached. This keeps hot entries
+    /// cached through scans over many keys that are each looked up once.
+    TinyLfu,
+}
+
+/// Limits on how much a cache holds. Unbounded by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheCapacity {
+    pub max_entries: Option<usize>,
+    /// The approximate size of the cached requests and responses, as JSON.
+    pub max_bytes: Option<usize>,
+    pub eviction: Eviction,
+}
+
+impl CacheCapacity {
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn eviction(mut self, eviction: Eviction) -> Self {
+        self.eviction = eviction;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Slot<V> {
+    value: V,
+    size: usize,
+    expires_at: Instant,
+    /// When it was inserted, to tell apart entries that expire at the same time.
+    inserted: u64,
+    used: u64,
+}
+
+/// A map of cached values that expire, within a [CacheCapacity].
+///
+/// Expired entries are dropped when they are looked up, when inserting
+/// (at most every second), and by [BoundedMap::purge_expired].
+#[derive(Debug)]
+pub struct BoundedMap<K, V> {
+    capacity: CacheCapacity,
+    entries: HashMap<K, Slot<V>>,
+    /// Keys by when they were last used, least recently used first.
+    recency: BTreeMap<u64, K>,
+    /// Keys by when they expire, first to expire first.
+    expiry: BTreeMap<(Instant, u64), K>,
+    uses: u64,
+    bytes: usize,
+    sketch: Option<FrequencySketch>,
+    next_purge: Instant,
+}
+
+impl<K: Hash + Eq + Clone, V> Default for BoundedMap<K, V> {
+    fn default() -> Self {
+        Self::new(CacheCapacity::default())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> BoundedMap<K, V> {
+    pub fn new(capacity: CacheCapacity) -> Self {
+        let sketch = (capacity.eviction == Eviction::TinyLfu)
+            .then(|| FrequencySketch::new(capacity.max_entries.unwrap_or(4096)));
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            expiry: BTreeMap::new(),
+            uses: 0,
+            bytes: 0,
+            sketch,
+            next_purge: Instant::now() + PURGE_INTERVAL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The approximate size of the entries, as given when inserting them.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// The value of `key`, unless it expired. Counts as a use of `key`, even if it isn't cached.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Like [BoundedMap::get], to update the value in place.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if let Some(sketch) = &mut self.sketch {
+            sketch.increment(key);
+        }
+        let expired = self.entries.get(key)?.expires_at <= Instant::now();
+        if expired {
+            self.remove(key);
+            return None;
+        }
+        self.uses += 1;
+        let slot = self.entries.get_mut(key)?;
+        if let Some(key) = self.recency.remove(&slot.used) {
+            self.recency.insert(self.uses, key);
+        }
+        slot.used = self.uses;
+        Some(&mut slot.value)
+    }
+
+    /// The value of `key`, even if it expired, without counting as a use.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|slot| &slot.value)
+    }
+
+    /// Like [BoundedMap::peek], to update the value in place.
+    pub fn peek_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key).map(|slot| &mut slot.value)
+    }
+
+    /// Cache `value` until `expires_at`, evicting other entries if it doesn't fit.
+    /// `size` is its approximate size, with the key, see [approximate_size].
+    /// Returns whether it was cached.
+    pub fn insert(&mut self, key: K, value: V, size: usize, expires_at: Instant) -> bool {
+        let now = Instant::now();
+        if now >= self.next_purge {
+            self.purge_expired();
+            self.next_purge = now + PURGE_INTERVAL;
+        }
+        if self.capacity.max_bytes.is_some_and(|max| size > max) {
+            self.remove(&key);
+            return false;
+        }
+        // Replacing a value doesn't have to be admitted again, and makes room for the new one.
+        let replacing = self.remove(&key).is_some();
+        while self.is_full(size) {
+            let Some((_, victim)) = self.recency.first_key_value() else {
+                break;
+            };
+            if let Some(sketch) = self.sketch.as_ref().filter(|_| !replacing) {
+                if sketch.frequency(&key) <= sketch.frequency(victim) {
+                    return false;
+                }
+            }
+            let victim = victim.clone();
+            self.remove(&victim);
+        }
+        self.uses += 1;
+        self.bytes += size;
+        self.recency.insert(self.uses, key.clone());
+        self.expiry.insert((expires_at, self.uses), key.clone());
+        let slot = Slot {
+            value,
+            size,
+            expires_at,
+            inserted: self.uses,
+            used: self.uses,
+        };
+        self.entries.insert(key, slot);
+        true
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let slot = self.entries.remove(key)?;
+        self.recency.remove(&slot.used);
+        self.expiry.remove(&(slot.expires_at, slot.inserted));
+        self.bytes -= slot.size;
+        Some(slot.value)
+    }
+
+    /// Drop all expired entries. Returns how many there were.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let mut purged = 0;
+        while let Some(entry) = self.expiry.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let key = entry.remove();
+            self.remove(&key);
+            purged += 1;
+        }
+        purged
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.expiry.clear();
+        self.bytes = 0;
+    }
+
+    fn is_full(&self, size: usize) -> bool {
+        self.capacity
+            .max_entries
+            .is_some_and(|max| self.entries.len() >= max)
+            || self
+                .capacity
+                .max_bytes
+                .is_some_and(|max| self.bytes + size > max)
+    }
+}
+
+/// Approximately how many bytes `value` takes up as JSON.
+pub fn approximate_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) => 4,
+        Value::Number(_) => 8,
+        Value::String(s) => s.len() + 2,
+        Value::Array(values) => {
+            values
+                .iter()
+                .map(|v| approximate_size(v) + 1)
+                .sum::<usize>()
+                + 2
+        }
+        Value::Object(fields) => {
+            fields
+                .iter()
+                .map(|(k, v)| k.len() + approximate_size(v) + 4)
+                .sum::<usize>()
+                + 2
+        }
+    }
+}
+
+/// A count-min sketch of how often keys were used lately, with 4 bit counters
+/// that are halved every `10 * width` uses so that old uses count for less.
+#[derive(Debug)]
+struct FrequencySketch {
+    rows: [Vec<u8>; 4],
+    mask: usize,
+    additions: usize,
+    sample_size: usize,
+    hasher: RandomState,
+}
+
+impl FrequencySketch {
+    fn new(entries: usize) -> Self {
+        let width = entries.max(16).next_power_of_two();
+        Self {
+            rows: std::array::from_fn(|_| vec![0; width]),
+            mask: width - 1,
+            additions: 0,
+            sample_size: 10 * width,
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn indexes(&self, key: &impl Hash) -> [usize; 4] {
+        let hash = self.hasher.hash_one(key);
+        std::array::from_fn(|row| {
+            let hash = hash
+                .wrapping_add(row as u64)
+                .wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            (hash >> 32) as usize & self.mask
+        })
+    }
+
+    fn increment(&mut self, key: &impl Hash) {
+        for (row, i) in self.indexes(key).into_iter().enumerate() {
+            let counter = &mut self.rows[row][i];
+            *counter = (*counter + 1).min(15);
+        }
+        self.additions += 1;
+        if self.additions >= self.sample_size {
+            for counter in self.rows.iter_mut().flatten() {
+                *counter /= 2;
+            }
+            self.additions /= 2;
+        }
+    }
+
+    fn frequency(&self, key: &impl Hash) -> u8 {
+        let indexes = self.indexes(key);
+        (0..4)
+            .map(|row| self.rows[row][indexes[row]])
+            .min()
+            .unwrap()
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::future::{ready, BoxFuture};
+use serde_json::Value;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use tower::{BoxError, Layer, Service};
+
+use crate::service::{errors::is_node_error, rpc_sender_impl::SolanaClientRequest};
+
+/// The message of the [ClientError] returned while the circuit is open.
+pub const CIRCUIT_OPEN: &str = "Circuit breaker is open";
+
+/// True if the request was rejected by an open [CircuitBreaker].
+pub fn is_circuit_open(err: &BoxError) -> bool {
+    matches!(
+        err.downcast_ref::<ClientError>(),
+        Some(ClientError {
+            kind: ClientErrorKind::Custom(message),
+            ..
+        }) if message == CIRCUIT_OPEN
+    )
+}
+
+/// When a [CircuitBreaker] opens, and for how long.
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    consecutive_failures: u32,
+    error_rate: Option<(f64, usize, Duration)>,
+    open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    /// Opens after 5 consecutive failures, for 30 seconds.
+    fn default() -> Self {
+        Self {
+            consecutive_failures: 5,
+            error_rate: None,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn consecutive_failures(mut self, n: u32) -> Self {
+        self.consecutive_failures = n;
+        self
+    }
+
+    /// Also open when at least `rate` (between 0 and 1) of the requests in the last `window`
+    /// failed, once there were at least `min_requests` of them.
+    pub fn error_rate(mut self, rate: f64, min_requests: usize, window: Duration) -> Self {
+        self.error_rate = Some((rate, min_requests, window));
+        self
+    }
+
+    /// How long to fail fast before letting a probe request through.
+    pub fn open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through.
+    Closed,
+    /// Requests fail fast until the given time.
+    Open { until: Instant },
+    /// A single probe request is in flight since the given time, others fail fast.
+    HalfOpen { since: Instant },
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    /// Recent outcomes, `true` for failures.
+    outcomes: VecDeque<(Instant, bool)>,
+}
+
+impl Breaker {
+    /// Decide whether a request may go through, and whether it is a probe.
+    /// A probe that takes longer than `open_duration` (or whose future was dropped) is replaced.
+    fn admit(&mut self, config: &CircuitBreakerConfig) -> Option<bool> {
+        let now = Instant::now();
+        match self.state {
+            CircuitState::Closed => Some(false),
+            CircuitState::Open { until } if now < until => None,
+            CircuitState::HalfOpen { since } if now < since + config.open_duration => None,
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => {
+                self.state = CircuitState::HalfOpen { since: now };
+                Some(true)
+            }
+        }
+    }
+
+    fn record(&mut self, config: &CircuitBreakerConfig, probe: bool, failed: bool) {
+        let now = Instant::now();
+        if probe {
+            if failed {
+                tracing::warn!("circuit breaker probe failed, staying open");
+                self.state = CircuitState::Open {
+                    until: now + config.open_duration,
+                };
+            } else {
+                tracing::info!("circuit breaker probe succeeded, closing");
+                self.state = CircuitState::Closed;
+                self.consecutive_failures = 0;
+                self.outcomes.clear();
+            }
+            return;
+        }
+        if self.state != CircuitState::Closed {
+            return;
+        }
+
+        self.consecutive_failures = if failed {
+            self.consecutive_failures + 1
+        } else {
+            0
+        };
+        let mut trip = self.consecutive_failures >= config.consecutive_failures;
+        if let Some((max_rate, min_requests, window)) = config.error_rate {
+            self.outcomes.push_back((now, failed));
+            while self
+                .outcomes
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > window)
+            {
+                self.outcomes.pop_front();
+            }
+            let failures = self.outcomes.iter().filter(|(_, failed)| *failed).count();
+            let rate = failures as f64 / self.outcomes.len() as f64;
+            trip |= self.outcomes.len() >= min_requests && rate >= max_rate;
+        }
+        if trip {
+            tracing::warn!(
+                consecutive_failures = self.consecutive_failures,
+                "circuit breaker opening"
+            );
+            self.state = CircuitState::Open {
+                until: now + config.open_duration,
+            };
+        }
+    }
+}
+
+/// Stops sending requests to the inner service after too many failures, failing fast
+/// with a [ClientError] instead. Once `open_duration` has passed, one probe request is let through,
+/// which closes the circuit again if it succeeds.
+///
+/// Only errors that say something about the health of the RPC node count as failures,
+/// see [is_node_error]. Errors about the request itself, e.g. a transaction failing preflight,
+/// don't trip the breaker.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker<S> {
+    inner: S,
+    config: CircuitBreakerConfig,
+    breaker: Arc<Mutex<Breaker>>,
+}
+
+impl<S> CircuitBreaker<S> {
+    pub fn new(inner: S, config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner,
+            config,
+            breaker: Arc::new(Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                outcomes: VecDeque::new(),
+            })),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.breaker.lock().unwrap().state
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for CircuitBreaker<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let Some(probe) = self.breaker.lock().unwrap().admit(&self.config) else {
+            return Box::pin(ready(Err(Box::new(ClientError::new_with_request(
+                ClientErrorKind::Custom(CIRCUIT_OPEN.to_string()),
+                req.0,
+            )) as BoxError)));
+        };
+        let fut = self.inner.call(req);
+        let breaker = self.breaker.clone();
+        let config = self.config.clone();
+        Box::pin(async move {
+            let result = fut.await;
+            let failed = result.as_ref().is_err_and(is_node_error);
+            breaker.lock().unwrap().record(&config, probe, failed);
+            result
+        })
+    }
+}
+
+pub struct CircuitBreakerLayer {
+    config: CircuitBreakerConfig,
+}
+
+impl CircuitBreakerLayer {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Layer<S> for CircuitBreakerLayer {
+    type Service = CircuitBreaker<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CircuitBreaker::new(inner, self.config.clone())
+    }
+}
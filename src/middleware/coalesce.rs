@@ -0,0 +1,149 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use serde_json::Value;
+use solana_client::rpc_request::RpcRequest;
+use tower::{BoxError, Layer, Service};
+
+use crate::service::{hedge::is_idempotent, rpc_sender_impl::SolanaClientRequest};
+
+/// The error of a request that was shared by several callers. Its source is the original error,
+/// so helpers like [rpc_error](crate::service::errors::rpc_error) see through it.
+#[derive(Debug, Clone)]
+pub struct SharedError(Arc<BoxError>);
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for SharedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.0.as_ref().as_ref())
+    }
+}
+
+type SharedResponse = Shared<BoxFuture<'static, Result<Value, SharedError>>>;
+
+#[derive(Debug, Default)]
+struct InFlight {
+    requests: Mutex<HashMap<SolanaClientRequest, SharedResponse>>,
+    coalesced: AtomicUsize,
+}
+
+/// Sends only one of identical requests (same method and params) that are in flight
+/// at the same time, and gives its response or error to all of them.
+/// Requests that come in after the response are sent again.
+///
+/// Only methods without side effects are coalesced unless configured otherwise,
+/// see [is_idempotent].
+///
+/// To coalesce the misses of a [ResponseCacheLayer](super::ResponseCacheLayer), put it below the cache.
+#[derive(Debug, Clone)]
+pub struct Coalesce<S> {
+    inner: S,
+    methods: Option<Arc<HashSet<RpcRequest>>>,
+    in_flight: Arc<InFlight>,
+}
+
+impl<S> Service<SolanaClientRequest> for Coalesce<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let coalesce = match &self.methods {
+            Some(methods) => methods.contains(&req.0),
+            None => is_idempotent(&req.0),
+        };
+        if !coalesce {
+            return Box::pin(self.inner.call(req));
+        }
+        let mut requests = self.in_flight.requests.lock().unwrap();
+        let response = match requests.get(&req) {
+            Some(response) => {
+                self.in_flight.coalesced.fetch_add(1, Ordering::Relaxed);
+                response.clone()
+            }
+            None => {
+                let fut = self.inner.call(req.clone());
+                let in_flight = self.in_flight.clone();
+                let key = req.clone();
+                let response = async move {
+                    let result = fut.await.map_err(|e| SharedError(Arc::new(e)));
+                    in_flight.requests.lock().unwrap().remove(&key);
+                    result
+                }
+                .boxed()
+                .shared();
+                requests.insert(req, response.clone());
+                response
+            }
+        };
+        Box::pin(async move {
+            // The last caller to get the error gets the original one.
+            response
+                .await
+                .map_err(|e| Arc::try_unwrap(e.0).unwrap_or_else(|e| SharedError(e).into()))
+        })
+    }
+}
+
+/// Builds [Coalesce] services that share the requests in flight.
+#[derive(Debug, Clone, Default)]
+pub struct CoalesceLayer {
+    methods: Option<HashSet<RpcRequest>>,
+    in_flight: Arc<InFlight>,
+}
+
+impl CoalesceLayer {
+    /// Coalesces requests of all methods without side effects, see [is_idempotent].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Coalesce requests of these methods instead, e.g. to also leave out `GetLatestBlockhash`.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Some(methods.into_iter().collect());
+        self
+    }
+
+    /// How many requests got the response of an identical request in flight.
+    pub fn coalesced(&self) -> usize {
+        self.in_flight.coalesced.load(Ordering::Relaxed)
+    }
+}
+
+impl<S> Layer<S> for CoalesceLayer {
+    type Service = Coalesce<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Coalesce {
+            inner,
+            methods: self.methods.clone().map(Arc::new),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    collections::HashSet,
+    io,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde_json::{json, Value};
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use tower::{util::rng::Rng, BoxError, Layer, Service};
+
+use crate::service::rpc_sender_impl::SolanaClientRequest;
+
+/// What a [Fault::MalformedBody] response body looks like, e.g. an error page from a proxy.
+const MALFORMED_BODY: &[u8] = b"<html><body>502 Bad Gateway</body></html>";
+
+/// How much latency to add to a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Mostly short, with a long tail.
+    Exponential {
+        mean: Duration,
+    },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match *self {
+            Latency::Fixed(latency) => latency,
+            Latency::Uniform { min, max } => {
+                min + (max.saturating_sub(min)).mul_f64(rng.next_f64())
+            }
+            Latency::Exponential { mean } => mean.mul_f64(-(1.0 - rng.next_f64()).ln()),
+        }
+    }
+}
+
+/// A fault to inject into a request, see [FaultInjection].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// Fail as if the connection was lost, or timed out.
+    TransportError,
+    /// Respond with this HTTP status, and a `Retry-After` header if given.
+    HttpStatus {
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// Respond with a JSON-RPC error.
+    RpcError { code: i64, message: String },
+    /// Cut the response body in half, so it can't be parsed.
+    TruncatedBody,
+    /// Respond with a body that isn't JSON.
+    MalformedBody,
+    /// Report a `context.slot` this many slots behind the real one, as a lagging node would.
+    StaleSlot(u64),
+}
+
+impl Fault {
+    pub fn too_many_requests(retry_after: Option<Duration>) -> Self {
+        Fault::HttpStatus {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after,
+        }
+    }
+
+    pub fn service_unavailable(retry_after: Option<Duration>) -> Self {
+        Fault::HttpStatus {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            retry_after,
+        }
+    }
+
+    /// Whether the inner service is still called, to alter its response.
+    fn needs_response(&self) -> bool {
+        matches!(self, Fault::TruncatedBody | Fault::StaleSlot(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule<T> {
+    /// Method names, or all methods if `None`.
+    methods: Option<HashSet<String>>,
+    action: T,
+    probability: f64,
+}
+
+impl<T> Rule<T> {
+    fn new(methods: Option<Vec<RpcRequest>>, action: T, probability: f64) -> Self {
+        Self {
+            methods: methods.map(|methods| methods.iter().map(|m| m.to_string()).collect()),
+            action,
+            probability,
+        }
+    }
+
+    fn applies_to(&self, method: &str) -> bool {
+        self.methods
+            .as_ref()
+            .is_none_or(|methods| methods.contains(method))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct FaultConfig {
+    latencies: Vec<Rule<Latency>>,
+    faults: Vec<Rule<Fault>>,
+}
+
+/// The splitmix64 generator, so the same seed gives the same faults on every platform.
+#[derive(Debug)]
+struct SeededRng(u64);
+
+impl Rng for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Injects faults into requests, to test retry and failover configurations locally.
+/// Faults are picked at random, seeded with [FaultInjectionLayer::new], so a test run
+/// with the same requests in the same order sees the same faults.
+///
+/// It works at two levels:
+/// - Above an RPC service, on [SolanaClientRequest]s. Injected errors look like the ones
+///   [ParseResponseBody](crate::service::ParseResponseBody) returns, but HTTP errors
+///   can't carry a `Retry-After` header, and transport errors are I/O errors.
+/// - Between [HttpRequestLayer](crate::service::HttpRequestLayer) and the
+///   HTTP client, on [reqwest::Request]s. Faults are real HTTP responses, so e.g.
+///   a 429 `Retry-After` header reaches [TooManyRequestsRetry](super::TooManyRequestsRetry),
+///   and transport errors are request timeouts.
+#[derive(Debug, Clone)]
+pub struct FaultInjection<S> {
+    inner: S,
+    config: Arc<FaultConfig>,
+    rng: Arc<Mutex<SeededRng>>,
+}
+
+impl<S> FaultInjection<S> {
+    /// The latency to add to a request of `method`, and the fault to inject, if any.
+    fn plan(&self, method: &str) -> (Duration, Option<Fault>) {
+        let mut rng = self.rng.lock().unwrap();
+        let latency = self
+            .config
+            .latencies
+            .iter()
+            .filter(|rule| rule.applies_to(method))
+            .map(|rule| rule.action.sample(&mut *rng))
+            .sum();
+        let fault = self
+            .config
+            .faults
+            .iter()
+            .filter(|rule| rule.applies_to(method))
+            .find(|rule| rng.next_f64() < rule.probability)
+            .map(|rule| rule.action.clone());
+        (latency, fault)
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for FaultInjection<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let (latency, fault) = self.plan(&req.0.to_string());
+        if let Some(fault) = &fault {
+            tracing::debug!(method = %req.0, ?fault, "injecting fault");
+        }
+        let fut = match &fault {
+            Some(fault) if !fault.needs_response() => None,
+            _ => Some(self.inner.call(req)),
+        };
+        Box::pin(async move {
+            tokio::time::sleep(latency).await;
+            match (fault, fut) {
+                (None, Some(fut)) => fut.await,
+                (Some(Fault::TruncatedBody), Some(fut)) => {
+                    let body = json!({ "jsonrpc": "2.0", "result": fut.await?, "id": 0 });
+                    let body = body.to_string().into_bytes();
+                    Err(serde_json::from_slice::<Value>(&body[..body.len() / 2])
+                        .unwrap_err()
+                        .into())
+                }
+                (Some(Fault::StaleSlot(slots)), Some(fut)) => {
+                    let mut value = fut.await?;
+                    make_stale(&mut value, slots);
+                    Ok(value)
+                }
+                (Some(fault), _) => Err(injected_error(fault)),
+                (None, None) => unreachable!(),
+            }
+        })
+    }
+}
+
+/// The error for faults that replace the response entirely.
+fn injected_error(fault: Fault) -> BoxError {
+    match fault {
+        Fault::TransportError => Box::new(io::Error::new(
+            io::ErrorKind::ConnectionReset,
+            "injected transport error",
+        )),
+        Fault::HttpStatus {
+            status,
+            retry_after,
+        } => Box::new(
+            http_response(status, retry_after, Vec::new())
+                .error_for_status()
+                .unwrap_err(),
+        ),
+        Fault::RpcError { code, message } => Box::new(RpcError::RpcResponseError {
+            code,
+            message,
+            data: RpcResponseErrorData::Empty,
+        }),
+        Fault::MalformedBody => {
+            Box::new(serde_json::from_slice::<Value>(MALFORMED_BODY).unwrap_err())
+        }
+        fault => unreachable!("{fault:?} alters the response"),
+    }
+}
+
+impl<S> Service<reqwest::Request> for FaultInjection<S>
+where
+    S: Service<reqwest::Request, Response = reqwest::Response, Error = reqwest::Error>,
+    S::Future: Send + 'static,
+{
+    type Response = reqwest::Response;
+    type Error = reqwest::Error;
+
+    type Future = BoxFuture<'static, Result<reqwest::Response, reqwest::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: reqwest::Request) -> Self::Future {
+        let body: Value = req
+            .body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|body| serde_json::from_slice(body).ok())
+            .unwrap_or_default();
+        let method = body["method"].as_str().unwrap_or_default();
+        let (latency, fault) = self.plan(method);
+        if let Some(fault) = &fault {
+            tracing::debug!(method, ?fault, "injecting fault");
+        }
+        if fault == Some(Fault::TransportError) {
+            *req.timeout_mut() = Some(Duration::ZERO);
+        }
+        // Transport errors are real request timeouts: the request goes to the HTTP client
+        // with a zero timeout, which fails it before it reaches the server.
+        let fut = match &fault {
+            Some(fault) if !fault.needs_response() && *fault != Fault::TransportError => None,
+            _ => Some(self.inner.call(req)),
+        };
+        let id = body["id"].clone();
+        Box::pin(async move {
+            tokio::time::sleep(latency).await;
+            match (fault, fut) {
+                (None | Some(Fault::TransportError), Some(fut)) => fut.await,
+                (Some(Fault::TruncatedBody), Some(fut)) => {
+                    let response = fut.await?;
+                    let status = response.status();
+                    let body = response.bytes().await?;
+                    Ok(http_response(status, None, body.slice(..body.len() / 2)))
+                }
+                (Some(Fault::StaleSlot(slots)), Some(fut)) => {
+                    let response = fut.await?;
+                    let status = response.status();
+                    let body = response.bytes().await?;
+                    let body = match serde_json::from_slice::<Value>(&body) {
+                        Ok(mut json) => {
+                            make_stale(&mut json["result"], slots);
+                            json.to_string().into()
+                        }
+                        Err(_) => body,
+                    };
+                    Ok(http_response(status, None, body))
+                }
+                (
+                    Some(Fault::HttpStatus {
+                        status,
+                        retry_after,
+                    }),
+                    _,
+                ) => Ok(http_response(status, retry_after, Vec::new())),
+                (Some(Fault::RpcError { code, message }), _) => {
+                    let body = json!({
+                        "jsonrpc": "2.0",
+                        "error": { "code": code, "message": message },
+                        "id": id,
+                    });
+                    Ok(http_response(StatusCode::OK, None, body.to_string()))
+                }
+                (Some(Fault::MalformedBody), _) => {
+                    Ok(http_response(StatusCode::OK, None, MALFORMED_BODY))
+                }
+                (Some(fault), None) => unreachable!("{fault:?} alters the response"),
+                (None, None) => unreachable!(),
+            }
+        })
+    }
+}
+
+/// Move the `context.slot` of a response back by `slots`.
+fn make_stale(result: &mut Value, slots: u64) {
+    if let Some(slot) = result.pointer_mut("/context/slot") {
+        if let Some(stale) = slot.as_u64().map(|slot| slot.saturating_sub(slots)) {
+            *slot = json!(stale);
+        }
+    }
+}
+
+fn http_response(
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: impl Into<reqwest::Body>,
+) -> reqwest::Response {
+    let mut response = http::Response::builder().status(status);
+    if let Some(retry_after) = retry_after {
+        response = response.header(RETRY_AFTER, retry_after.as_secs());
+    }
+    response.body(body.into()).unwrap().into()
+}
+
+pub struct FaultInjectionLayer {
+    config: FaultConfig,
+    seed: u64,
+}
+
+impl FaultInjectionLayer {
+    /// No faults until some are added. The same `seed` injects the same faults.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            config: FaultConfig::default(),
+            seed,
+        }
+    }
+
+    /// Add latency to every request. Latencies from several calls add up.
+    pub fn latency(mut self, latency: Latency) -> Self {
+        self.config.latencies.push(Rule::new(None, latency, 1.0));
+        self
+    }
+
+    /// Add latency to requests of these methods.
+    pub fn latency_for(
+        mut self,
+        methods: impl IntoIterator<Item = RpcRequest>,
+        latency: Latency,
+    ) -> Self {
+        let methods = Some(methods.into_iter().collect());
+        self.config.latencies.push(Rule::new(methods, latency, 1.0));
+        self
+    }
+
+    /// Inject `fault` into a share of requests, given by `probability` between 0 and 1.
+    /// When several faults are added, the first one that is picked applies.
+    pub fn fault(mut self, fault: Fault, probability: f64) -> Self {
+        self.config.faults.push(Rule::new(None, fault, probability));
+        self
+    }
+
+    /// Like [FaultInjectionLayer::fault], for requests of these methods only.
+    pub fn fault_for(
+        mut self,
+        methods: impl IntoIterator<Item = RpcRequest>,
+        fault: Fault,
+        probability: f64,
+    ) -> Self {
+        let methods = Some(methods.into_iter().collect());
+        self.config
+            .faults
+            .push(Rule::new(methods, fault, probability));
+        self
+    }
+}
+
+impl<S> Layer<S> for FaultInjectionLayer {
+    type Service = FaultInjection<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FaultInjection {
+            inner,
+            config: Arc::new(self.config.clone()),
+            rng: Arc::new(Mutex::new(SeededRng(self.seed))),
+        }
+    }
+}
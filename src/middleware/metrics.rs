@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    hash::Hash,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tower::{BoxError, Layer, Service};
+
+use crate::service::{
+    rpc_sender_impl::SolanaClientRequest,
+    stats_updater::{RequestStats, StatsUpdater, TransportStats},
+};
+
+/// The `Content-Type` to serve [render_prometheus] output with.
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Records the stats of every request that goes through it, like an
+/// [RpcClientSender](crate::service::RpcClientSender) does, into a handle that can be rendered
+/// with [render_prometheus]. Useful when the client is built by one of the builders,
+/// which don't give access to the sender's own stats.
+#[derive(Debug, Clone)]
+pub struct Metrics<S> {
+    inner: S,
+    stats: Arc<RwLock<TransportStats>>,
+    endpoint: Option<String>,
+}
+
+impl<S> Metrics<S> {
+    pub fn new(inner: S, stats: Arc<RwLock<TransportStats>>) -> Self {
+        Self {
+            inner,
+            stats,
+            endpoint: None,
+        }
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for Metrics<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let updater = Arc::new(StatsUpdater::for_request(
+            self.stats.clone(),
+            req.0,
+            self.endpoint.clone(),
+        ));
+        let fut = updater.clone().sync_scope(|| self.inner.call(req));
+        Box::pin(async move {
+            let result = updater.clone().scope(fut).await;
+            updater.set_result(&result);
+            result
+        })
+    }
+}
+
+pub struct MetricsLayer {
+    stats: Arc<RwLock<TransportStats>>,
+    endpoint: Option<String>,
+}
+
+impl MetricsLayer {
+    pub fn new(stats: Arc<RwLock<TransportStats>>) -> Self {
+        Self {
+            stats,
+            endpoint: None,
+        }
+    }
+
+    /// Count requests under this endpoint, unless a service further down
+    /// reports which endpoints it sent them to.
+    pub fn endpoint(mut self, url: impl ToString) -> Self {
+        self.endpoint = Some(url.to_string());
+        self
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            stats: self.stats.clone(),
+            endpoint: self.endpoint.clone(),
+        }
+    }
+}
+
+/// Render transport stats in the Prometheus text exposition format,
+/// e.g. to serve them from an existing HTTP server with [PROMETHEUS_CONTENT_TYPE].
+pub fn render_prometheus(stats: &TransportStats) -> String {
+    let mut out = String::new();
+
+    counter(
+        &mut out,
+        "solana_rpc_requests_total",
+        "Number of RPC requests issued.",
+        [("", stats.request_count as f64)],
+    );
+    counter(
+        &mut out,
+        "solana_rpc_request_seconds_total",
+        "Total time spent transacting with the RPC server.",
+        [("", stats.elapsed_time.as_secs_f64())],
+    );
+    counter(
+        &mut out,
+        "solana_rpc_rate_limited_seconds_total",
+        "Total time spent waiting because of rate limiting.",
+        [("", stats.rate_limited_time.as_secs_f64())],
+    );
+    counter(
+        &mut out,
+        "solana_rpc_queued_seconds_total",
+        "Total time spent waiting for the service to become ready.",
+        [("", stats.queued_time.as_secs_f64())],
+    );
+    counter(
+        &mut out,
+        "solana_rpc_retries_total",
+        "Number of times a request was retried.",
+        [("", stats.retry_count as f64)],
+    );
+    counter(
+        &mut out,
+        "solana_rpc_hedged_requests_total",
+        "Number of requests also sent to a second endpoint.",
+        [("", stats.hedged_request_count as f64)],
+    );
+
+    let methods = labelled(&stats.methods, |method| format!("method=\"{method}\""));
+    let endpoints = labelled(&stats.endpoints, |url| {
+        format!("endpoint=\"{}\"", escape(url))
+    });
+    request_stats(&mut out, "solana_rpc_method", "method", &methods);
+    request_stats(&mut out, "solana_rpc_endpoint", "endpoint", &endpoints);
+
+    counter(
+        &mut out,
+        "solana_rpc_cache_hits_total",
+        "Number of requests answered from the cache, by method.",
+        methods
+            .iter()
+            .filter(|(_, s)| s.cache_hits + s.cache_misses > 0)
+            .map(|(labels, s)| (labels.as_str(), s.cache_hits as f64)),
+    );
+    counter(
+        &mut out,
+        "solana_rpc_cache_misses_total",
+        "Number of requests the cache had to pass on, by method.",
+        methods
+            .iter()
+            .filter(|(_, s)| s.cache_hits + s.cache_misses > 0)
+            .map(|(labels, s)| (labels.as_str(), s.cache_misses as f64)),
+    );
+    counter(
+        &mut out,
+        "solana_rpc_cache_stale_total",
+        "Number of requests answered with an expired response from the cache, by method.",
+        methods
+            .iter()
+            .filter(|(_, s)| s.cache_hits + s.cache_misses > 0)
+            .map(|(labels, s)| (labels.as_str(), s.stale_responses as f64)),
+    );
+    out
+}
+
+/// Label each entry, sorted by label so the output is stable.
+fn labelled<K: Hash + Eq>(
+    stats: &HashMap<K, RequestStats>,
+    label: impl Fn(&K) -> String,
+) -> Vec<(String, &RequestStats)> {
+    let mut stats: Vec<_> = stats.iter().map(|(k, s)| (label(k), s)).collect();
+    stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+    stats
+}
+
+/// Request and error counters, and a latency histogram.
+fn request_stats(out: &mut String, prefix: &str, by: &str, stats: &[(String, &RequestStats)]) {
+    counter(
+        out,
+        &format!("{prefix}_requests_total"),
+        &format!("Number of requests, by {by}."),
+        stats
+            .iter()
+            .map(|(labels, s)| (labels.as_str(), s.request_count as f64)),
+    );
+
+    let errors: Vec<(String, f64)> = stats
+        .iter()
+        .flat_map(|(labels, s)| {
+            let mut codes: Vec<_> = s.errors_by_code.iter().collect();
+            codes.sort();
+            let without_code = s.error_count - codes.iter().map(|(_, n)| **n).sum::<usize>();
+            codes
+                .into_iter()
+                .map(|(code, n)| (format!("{labels},code=\"{code}\""), *n as f64))
+                .chain(
+                    (without_code > 0)
+                        .then(|| (format!("{labels},code=\"none\""), without_code as f64)),
+                )
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    counter(
+        out,
+        &format!("{prefix}_errors_total"),
+        &format!("Number of failed requests, by {by} and JSON-RPC error code."),
+        errors.iter().map(|(labels, n)| (labels.as_str(), *n)),
+    );
+
+    let name = format!("{prefix}_request_duration_seconds");
+    writeln!(out, "# HELP {name} Request latency, by {by}.").unwrap();
+    writeln!(out, "# TYPE {name} histogram").unwrap();
+    for (labels, s) in stats {
+        for le in LATENCY_BUCKETS {
+            let count = s.latency.count_below(Duration::from_secs_f64(le));
+            writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {count}").unwrap();
+        }
+        let count = s.latency.count();
+        let sum = s.latency.sum().as_secs_f64();
+        writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}").unwrap();
+        writeln!(out, "{name}_sum{{{labels}}} {sum}").unwrap();
+        writeln!(out, "{name}_count{{{labels}}} {count}").unwrap();
+    }
+}
+
+fn counter<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (&'a str, f64)>,
+) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} counter").unwrap();
+    for (labels, value) in samples {
+        match labels {
+            "" => writeln!(out, "{name} {value}"),
+            labels => writeln!(out, "{name}{{{labels}}} {value}"),
+        }
+        .unwrap();
+    }
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
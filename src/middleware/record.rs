@@ -0,0 +1,84 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tower::{BoxError, Layer, Service};
+
+use crate::service::{cassette::CassetteEntry, rpc_sender_impl::SolanaClientRequest};
+
+/// Writes every request and its response to a cassette file, one JSON line each,
+/// to be served later by a [ReplayService](crate::service::ReplayService).
+#[derive(Debug, Clone)]
+pub struct Record<S> {
+    inner: S,
+    file: Arc<Mutex<File>>,
+}
+
+impl<S> Service<SolanaClientRequest> for Record<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let fut = self.inner.call(req.clone());
+        let file = self.file.clone();
+        Box::pin(async move {
+            let result = fut.await;
+            let line = serde_json::to_string(&CassetteEntry::new(&req, &result)).unwrap();
+            // A failed write shouldn't fail the request.
+            if let Err(e) = writeln!(file.lock().unwrap(), "{line}") {
+                tracing::error!(err = %e, "failed to record response");
+            }
+            result
+        })
+    }
+}
+
+pub struct RecordLayer {
+    file: Arc<Mutex<File>>,
+}
+
+impl RecordLayer {
+    /// Record to `path`, replacing what it contained.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_file(File::create(path)?))
+    }
+
+    /// Record to the end of `path`, creating it if needed.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_file(file))
+    }
+
+    fn from_file(file: File) -> Self {
+        Self {
+            file: Arc::new(Mutex::new(file)),
+        }
+    }
+}
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = Record<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Record {
+            inner,
+            file: self.file.clone(),
+        }
+    }
+}
@@ -1,22 +1,143 @@
 use reqwest::header::RETRY_AFTER;
 use reqwest::StatusCode;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use tokio::time::Sleep;
-use tower::retry;
+use tower::retry::{
+    self,
+    budget::{Budget, TpsBudget},
+};
+use tower::util::rng::{HasherRng, Rng};
 
+use crate::service::stats_updater::StatsUpdater;
+
+/// How to randomize the backoff between retries, so that many clients rate limited
+/// at the same time don't all retry at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait exactly `base_delay * 2^attempt`, capped at `max_delay`.
+    None,
+    /// Wait a random time between zero and `base_delay * 2^attempt`, capped at `max_delay`.
+    Full,
+    /// Wait a random time between `base_delay` and three times the previous wait,
+    /// capped at `max_delay`.
+    Decorrelated,
+}
+
+/// Retries HTTP 429 responses, waiting for as long as the `Retry-After` header says
+/// (either in seconds or as an HTTP date), or else backing off, see
+/// [TooManyRequestsRetry::backoff].
+///
+/// The time spent waiting is added to the `rate_limited_time` of the client's transport stats.
+///
+/// A [TpsBudget] can be shared between policies, even across clients,
+/// so that a provider outage doesn't turn into a retry storm.
 #[derive(Debug, Clone)]
 pub struct TooManyRequestsRetry {
     retries_remaining: usize,
     rate_limited_time: Duration,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: Jitter,
+    exponential: bool,
+    max_retry_after: Duration,
+    max_total_wait: Option<Duration>,
+    budget: Option<Arc<TpsBudget>>,
+    attempt: u32,
+    last_delay: Duration,
+    /// Shared by the clones made for each request, so that they draw different jitter.
+    rng: Arc<Mutex<HasherRng>>,
 }
 
 impl TooManyRequestsRetry {
+    /// Waits a fixed 500ms between retries, without jitter or a budget, unless `Retry-After`
+    /// asks for less than 2 minutes. Backing off exponentially, jitter and budgets are opt-in.
     pub fn new(num_retries: usize) -> Self {
         Self {
             retries_remaining: num_retries,
             rate_limited_time: Default::default(),
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_millis(500),
+            jitter: Jitter::None,
+            exponential: false,
+            max_retry_after: Duration::from_secs(120),
+            max_total_wait: None,
+            budget: None,
+            attempt: 0,
+            last_delay: Duration::ZERO,
+            rng: Default::default(),
         }
     }
+
+    /// Wait `base_delay * 2^attempt` between retries, capped at `max_delay`,
+    /// instead of a fixed 500ms. `Retry-After` waits are capped at `max_delay` too.
+    pub fn backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self.exponential = true;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Ignore `Retry-After` waits of this long or longer, and back off instead. 2 minutes by default.
+    pub fn max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    /// Give up once waiting again would take the total time spent waiting for this request
+    /// over `max_total_wait`.
+    pub fn max_total_wait(mut self, max_total_wait: Duration) -> Self {
+        self.max_total_wait = Some(max_total_wait);
+        self
+    }
+
+    /// Every request deposits into the budget, and every retry withdraws from it.
+    /// Once it is empty, rate limited requests fail without being retried.
+    pub fn budget(mut self, budget: Arc<TpsBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The total time spent waiting for this request.
+    pub fn rate_limited_time(&self) -> Duration {
+        self.rate_limited_time
+    }
+
+    fn backoff_delay(&mut self) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(self.attempt))
+            .min(self.max_delay);
+        let delay = match self.jitter {
+            Jitter::None => exponential,
+            Jitter::Full => exponential.mul_f64(self.rng.lock().unwrap().next_f64()),
+            Jitter::Decorrelated => {
+                let upper = self.last_delay.saturating_mul(3).max(self.base_delay);
+                let extra = upper.saturating_sub(self.base_delay);
+                let random = self.rng.lock().unwrap().next_f64();
+                (self.base_delay + extra.mul_f64(random)).min(self.max_delay)
+            }
+        };
+        self.last_delay = delay;
+        delay
+    }
+}
+
+/// Parse a `Retry-After` value, either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
 }
 
 impl retry::Policy<reqwest::Request, reqwest::Response, reqwest::Error> for TooManyRequestsRetry {
@@ -27,39 +148,70 @@ impl retry::Policy<reqwest::Request, reqwest::Response, reqwest::Error> for TooM
         _req: &mut reqwest::Request,
         result: &mut Result<reqwest::Response, reqwest::Error>,
     ) -> Option<Self::Future> {
-        if let Ok(response) = result {
-            if !response.status().is_success() {
-                if response.status() == StatusCode::TOO_MANY_REQUESTS && self.retries_remaining > 0
-                {
-                    let mut duration = Duration::from_millis(500);
-                    if let Some(retry_after) = response.headers().get(RETRY_AFTER) {
-                        if let Ok(retry_after) = retry_after.to_str() {
-                            if let Ok(retry_after) = retry_after.parse::<u64>() {
-                                if retry_after < 120 {
-                                    duration = Duration::from_secs(retry_after);
-                                }
-                            }
-                        }
-                    }
-
-                    self.retries_remaining -= 1;
-                    tracing::debug!(
-                                "Too many requests: server responded with {:?}, {} retries left, pausing for {:?}",
-                                response, self.retries_remaining, duration
-                            );
-
-                    self.rate_limited_time += duration;
-                    return Some(tokio::time::sleep(duration));
-                }
+        if self.attempt == 0 {
+            if let Some(budget) = &self.budget {
+                budget.deposit();
             }
         }
-        None
+        let Ok(response) = result else {
+            return None;
+        };
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || self.retries_remaining == 0 {
+            return None;
+        }
+
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after)
+            .filter(|&retry_after| retry_after < self.max_retry_after);
+        let duration = match retry_after {
+            Some(retry_after) if self.exponential => retry_after.min(self.max_delay),
+            Some(retry_after) => retry_after,
+            None => self.backoff_delay(),
+        };
+        if self
+            .max_total_wait
+            .is_some_and(|max| self.rate_limited_time + duration > max)
+        {
+            tracing::debug!(
+                rate_limited_time = ?self.rate_limited_time,
+                "Too many requests: giving up, waiting any longer would exceed the max total wait"
+            );
+            return None;
+        }
+        if self
+            .budget
+            .as_ref()
+            .is_some_and(|budget| !budget.withdraw())
+        {
+            tracing::debug!("Too many requests: giving up, the retry budget is exhausted");
+            return None;
+        }
+
+        self.retries_remaining -= 1;
+        self.attempt += 1;
+        tracing::Span::current().record("attempt", self.attempt);
+        tracing::debug!(
+            "Too many requests: server responded with {}, {} retries left, pausing for {:?}",
+            response.status(),
+            self.retries_remaining,
+            duration
+        );
+
+        self.rate_limited_time += duration;
+        StatsUpdater::with_current(|stats| {
+            stats.add_rate_limited_time(duration);
+            stats.add_retry();
+        });
+        Some(tokio::time::sleep(duration))
     }
 
     fn clone_request(&mut self, req: &reqwest::Request) -> Option<reqwest::Request> {
         let mut request = reqwest::Request::new(req.method().clone(), req.url().clone());
         *request.headers_mut() = req.headers().clone();
-        *request.timeout_mut() = req.timeout().copied().clone();
+        *request.timeout_mut() = req.timeout().copied();
         *request.body_mut() = req
             .body()
             .and_then(|b| b.as_bytes())
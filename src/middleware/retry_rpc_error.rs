@@ -0,0 +1,125 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use serde_json::Value;
+use solana_client::{
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+        JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_request::RpcRequest,
+};
+use tokio::time::Sleep;
+use tower::{retry, BoxError};
+
+use crate::service::{
+    errors::{is_transport_error, rpc_error_code},
+    hedge::is_idempotent,
+    rpc_sender_impl::SolanaClientRequest,
+    stats_updater::StatsUpdater,
+};
+
+/// JSON-RPC error codes that are retried by default. These are usually transient,
+/// e.g. the node is catching up, or has not seen the requested slot yet.
+pub const DEFAULT_RETRY_CODES: [i64; 4] = [
+    JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE,
+    JSON_RPC_SERVER_ERROR_BLOCK_STATUS_NOT_AVAILABLE_YET,
+    JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+];
+
+/// A retry policy for [SolanaClientRequest] services, which retries on chosen JSON-RPC error codes
+/// and on transport errors (e.g. connection resets and timeouts).
+///
+/// By default every method except those with side effects (`SendTransaction`, `RequestAirdrop`)
+/// is retried. Setting an explicit allowlist with [RpcErrorRetry::methods] replaces that.
+///
+/// Like any [tower::retry] policy, this needs the inner service to be `Clone`.
+#[derive(Debug, Clone)]
+pub struct RpcErrorRetry {
+    retries_remaining: usize,
+    delay: Duration,
+    codes: Arc<HashSet<i64>>,
+    retry_transport_errors: bool,
+    methods: Option<Arc<HashSet<RpcRequest>>>,
+    attempt: u32,
+}
+
+impl RpcErrorRetry {
+    /// Retries [DEFAULT_RETRY_CODES] and transport errors, pausing for 200ms in between.
+    pub fn new(num_retries: usize) -> Self {
+        Self {
+            retries_remaining: num_retries,
+            delay: Duration::from_millis(200),
+            codes: Arc::new(DEFAULT_RETRY_CODES.into_iter().collect()),
+            retry_transport_errors: true,
+            methods: None,
+            attempt: 0,
+        }
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Replace the set of JSON-RPC error codes that are retried.
+    pub fn codes(mut self, codes: impl IntoIterator<Item = i64>) -> Self {
+        self.codes = Arc::new(codes.into_iter().collect());
+        self
+    }
+
+    pub fn retry_transport_errors(mut self, retry_transport_errors: bool) -> Self {
+        self.retry_transport_errors = retry_transport_errors;
+        self
+    }
+
+    /// Only retry these methods.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Some(Arc::new(methods.into_iter().collect()));
+        self
+    }
+
+    fn should_retry(&self, method: &RpcRequest, err: &BoxError) -> bool {
+        let allowed = match &self.methods {
+            Some(methods) => methods.contains(method),
+            None => is_idempotent(method),
+        };
+        allowed
+            && (rpc_error_code(err).is_some_and(|code| self.codes.contains(&code))
+                || (self.retry_transport_errors && is_transport_error(err)))
+    }
+}
+
+impl retry::Policy<SolanaClientRequest, Value, BoxError> for RpcErrorRetry {
+    type Future = Sleep;
+
+    fn retry(
+        &mut self,
+        req: &mut SolanaClientRequest,
+        result: &mut Result<Value, BoxError>,
+    ) -> Option<Self::Future> {
+        let Err(err) = result else {
+            return None;
+        };
+        if self.retries_remaining == 0 || !self.should_retry(&req.0, err) {
+            return None;
+        }
+        self.retries_remaining -= 1;
+        self.attempt += 1;
+        tracing::Span::current().record("attempt", self.attempt);
+        tracing::debug!(
+            method = %req.0,
+            ?err,
+            retries_remaining = self.retries_remaining,
+            delay = ?self.delay,
+            "retrying request"
+        );
+        StatsUpdater::with_current(StatsUpdater::add_retry);
+        Some(tokio::time::sleep(self.delay))
+    }
+
+    fn clone_request(&mut self, req: &SolanaClientRequest) -> Option<SolanaClientRequest> {
+        Some(req.clone())
+    }
+}
@@ -0,0 +1,129 @@
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tower::{BoxError, Layer, Service};
+use tracing::{field::Empty, Instrument};
+
+use crate::service::rpc_sender_impl::SolanaClientRequest;
+
+/// How much of each response a [Trace] service logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyLogging {
+    Off,
+    /// At most this many bytes of the response.
+    Truncated(usize),
+    Full,
+}
+
+/// Opens an `rpc_request` span for every request, with the `method`.
+/// Services further down fill in the rest of the span's fields as the request goes through them:
+/// - `id`, the JSON-RPC request ID, and `endpoint`, from [HttpJsonRpcRequestService](crate::service::HttpJsonRpcRequestService)
+/// - `attempt`, from retry policies, starting at 1 for the first retry
+/// - `status` and `body_size`, from [ParseResponseBody](crate::service::ParseResponseBody)
+///
+/// Responses are logged at debug level, as configured with [BodyLogging].
+#[derive(Debug, Clone)]
+pub struct Trace<S> {
+    inner: S,
+    body_logging: BodyLogging,
+}
+
+impl<S> Trace<S> {
+    pub fn new(inner: S, body_logging: BodyLogging) -> Self {
+        Self {
+            inner,
+            body_logging,
+        }
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for Trace<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+
+    type Future = BoxFuture<'static, Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        let span = tracing::info_span!(
+            "rpc_request",
+            method = %req.0,
+            id = Empty,
+            endpoint = Empty,
+            attempt = Empty,
+            status = Empty,
+            body_size = Empty,
+        );
+        let fut = span.in_scope(|| self.inner.call(req));
+        let body_logging = self.body_logging;
+        Box::pin(
+            async move {
+                let result = fut.await;
+                match &result {
+                    Ok(value) => match body_logging {
+                        BodyLogging::Off => tracing::debug!("rpc response"),
+                        BodyLogging::Truncated(max) => {
+                            tracing::debug!(body = truncate(value.to_string(), max), "rpc response")
+                        }
+                        BodyLogging::Full => tracing::debug!(body = %value, "rpc response"),
+                    },
+                    Err(e) => tracing::debug!(err = %e, "rpc request failed"),
+                }
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+fn truncate(mut body: String, max: usize) -> String {
+    if body.len() > max {
+        let mut end = max;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+        body.push_str("...");
+    }
+    body
+}
+
+pub struct TraceLayer {
+    body_logging: BodyLogging,
+}
+
+impl TraceLayer {
+    /// Doesn't log response bodies.
+    pub fn new() -> Self {
+        Self {
+            body_logging: BodyLogging::Off,
+        }
+    }
+
+    pub fn body_logging(mut self, body_logging: BodyLogging) -> Self {
+        self.body_logging = body_logging;
+        self
+    }
+}
+
+impl Default for TraceLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = Trace<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Trace::new(inner, self.body_logging)
+    }
+}
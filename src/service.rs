@@ -1,11 +1,42 @@
+pub mod balance;
 pub mod builder;
+#[cfg(feature = "test-utils")]
+pub mod cassette;
+pub mod endpoint;
+pub mod errors;
+pub mod failover;
+#[cfg(feature = "test-utils")]
+pub mod fake_ledger;
+pub mod health;
+pub mod hedge;
 pub mod http_request_builder;
+#[cfg(feature = "test-utils")]
+pub mod mock;
 pub mod parse_response_body;
+pub mod quorum;
+pub mod redact;
+pub mod router;
 pub mod rpc_sender_impl;
+pub mod slot_aware;
 pub mod stats_updater;
 
 pub use serde_json::Value;
 pub use solana_client::rpc_request::RpcRequest;
 
+pub use balance::{BalanceService, BalanceStrategy};
+#[cfg(feature = "test-utils")]
+pub use cassette::{MatchRule, ReplayService};
+pub use endpoint::Endpoint;
+pub use failover::FailoverService;
+#[cfg(feature = "test-utils")]
+pub use fake_ledger::FakeLedger;
+pub use health::{EndpointHealth, Health, HealthChecker};
+pub use hedge::HedgeService;
 pub use http_request_builder::{HttpJsonRpcRequestService, HttpRequestLayer};
+#[cfg(feature = "test-utils")]
+pub use mock::MockService;
 pub use parse_response_body::{ParseResponseBody, ParseResponseBodyLayer};
+pub use quorum::{QuorumError, QuorumService, SlotAgreement};
+pub use redact::Redactor;
+pub use router::{BoxRpcService, RouterService};
+pub use slot_aware::{EndpointSlot, SlotAwareService};
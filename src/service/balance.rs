@@ -0,0 +1,199 @@
+use std::{
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use tower::{
+    balance::p2c::Balance,
+    discover::ServiceList,
+    load::{CompleteOnResponse, PeakEwma, PendingRequests},
+    BoxError, Service,
+};
+
+use super::rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse};
+
+/// How a [BalanceService] picks the backend for each request.
+#[derive(Debug, Clone)]
+pub enum BalanceStrategy {
+    /// Cycle through the backends in order.
+    RoundRobin,
+    /// Like round-robin, but each backend gets a share of the requests proportional to its weight.
+    /// Weights are given in the same order as the backends.
+    Weighted(Vec<u32>),
+    /// Pick two backends at random, and use the one with fewer requests in flight.
+    LeastOutstanding,
+    /// Pick two backends at random, and use the one with the lower peak-EWMA latency,
+    /// see [tower::load::PeakEwma].
+    PeakEwma {
+        /// Latency assumed for a backend before any response has been seen.
+        default_rtt: Duration,
+        /// How quickly past latencies are forgotten.
+        decay: Duration,
+    },
+}
+
+impl BalanceStrategy {
+    /// [BalanceStrategy::PeakEwma] with reasonable defaults for a remote RPC node.
+    pub fn peak_ewma() -> Self {
+        Self::PeakEwma {
+            default_rtt: Duration::from_millis(100),
+            decay: Duration::from_secs(10),
+        }
+    }
+}
+
+type P2c<S> = Balance<ServiceList<Vec<S>>, SolanaClientRequest>;
+
+enum Balancer<S> {
+    RoundRobin(WeightedRoundRobin<S>),
+    LeastOutstanding(P2c<PendingRequests<S>>),
+    PeakEwma(P2c<PeakEwma<S>>),
+}
+
+/// Spreads requests over a pool of backends according to a [BalanceStrategy].
+///
+/// Unlike [FailoverService](super::FailoverService), a failed request is not retried
+/// on another backend. Stack both if you want that.
+pub struct BalanceService<S> {
+    balancer: Balancer<S>,
+}
+
+impl<S> BalanceService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+{
+    /// Panics if `services` is empty, or if the strategy is [BalanceStrategy::Weighted]
+    /// with a different number of weights than services, or with all weights zero.
+    pub fn new(services: Vec<S>, strategy: BalanceStrategy) -> Self {
+        assert!(
+            !services.is_empty(),
+            "BalanceService requires at least one backend"
+        );
+        let balancer = match strategy {
+            BalanceStrategy::RoundRobin => {
+                let weights = vec![1; services.len()];
+                Balancer::RoundRobin(WeightedRoundRobin::new(services, weights))
+            }
+            BalanceStrategy::Weighted(weights) => {
+                Balancer::RoundRobin(WeightedRoundRobin::new(services, weights))
+            }
+            BalanceStrategy::LeastOutstanding => {
+                let services = services
+                    .into_iter()
+                    .map(|s| PendingRequests::new(s, CompleteOnResponse::default()))
+                    .collect();
+                Balancer::LeastOutstanding(Balance::new(ServiceList::new(services)))
+            }
+            BalanceStrategy::PeakEwma { default_rtt, decay } => {
+                let decay_ns = decay.as_nanos() as f64;
+                let services = services
+                    .into_iter()
+                    .map(|s| PeakEwma::new(s, default_rtt, decay_ns, CompleteOnResponse::default()))
+                    .collect();
+                Balancer::PeakEwma(Balance::new(ServiceList::new(services)))
+            }
+        };
+        Self { balancer }
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for BalanceService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match &mut self.balancer {
+            Balancer::RoundRobin(s) => s.poll_ready(cx),
+            Balancer::LeastOutstanding(s) => s.poll_ready(cx),
+            Balancer::PeakEwma(s) => s.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        match &mut self.balancer {
+            Balancer::RoundRobin(s) => Box::pin(s.call(request)),
+            Balancer::LeastOutstanding(s) => Box::pin(s.call(request)),
+            Balancer::PeakEwma(s) => Box::pin(s.call(request)),
+        }
+    }
+}
+
+/// Smooth weighted round-robin, as done by nginx. Backends with a higher weight
+/// are picked more often, without being picked several times in a row when avoidable.
+struct WeightedRoundRobin<S> {
+    services: Vec<S>,
+    weights: Vec<i64>,
+    current: Vec<i64>,
+    ready_index: Option<usize>,
+}
+
+impl<S> WeightedRoundRobin<S> {
+    fn new(services: Vec<S>, weights: Vec<u32>) -> Self {
+        assert_eq!(
+            services.len(),
+            weights.len(),
+            "BalanceStrategy::Weighted requires one weight per backend"
+        );
+        assert!(
+            weights.iter().any(|w| *w > 0),
+            "BalanceStrategy::Weighted requires at least one non-zero weight"
+        );
+        Self {
+            current: vec![0; services.len()],
+            weights: weights.into_iter().map(i64::from).collect(),
+            services,
+            ready_index: None,
+        }
+    }
+
+    fn next_index(&mut self) -> usize {
+        let total: i64 = self.weights.iter().sum();
+        for (current, weight) in self.current.iter_mut().zip(&self.weights) {
+            *current += weight;
+        }
+        let (index, _) = self
+            .current
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.weights[*i] > 0)
+            .max_by_key(|(i, current)| (**current, std::cmp::Reverse(*i)))
+            .unwrap();
+        self.current[index] -= total;
+        index
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for WeightedRoundRobin<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError>,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = S::Future;
+
+    /// Once a backend is picked, we wait on its readiness rather than skipping it,
+    /// so that the distribution of requests is respected.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let index = match self.ready_index {
+            Some(index) => index,
+            None => {
+                let index = self.next_index();
+                self.ready_index = Some(index);
+                index
+            }
+        };
+        self.services[index].poll_ready(cx)
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let index = self.ready_index.take().expect("called before ready");
+        self.services[index].call(request)
+    }
+}
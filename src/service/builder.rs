@@ -1,25 +1,61 @@
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
-use reqwest::Url;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
 use serde_json::Value;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
 use solana_sdk::commitment_config::CommitmentConfig;
-use tower::{
-    retry::RetryLayer, service_fn, util::ServiceFn, BoxError, Layer, Service, ServiceBuilder,
-};
-
-use crate::middleware::TooManyRequestsRetry;
+use tower::{service_fn, util::ServiceFn, BoxError, Layer, Service, ServiceBuilder};
 
 use super::{
+    balance::{BalanceService, BalanceStrategy},
+    failover::{FailoverService, OnServed},
+    health::HealthChecker,
+    hedge::HedgeService,
+    quorum::{QuorumService, SlotAgreement},
+    redact::Redactor,
+    router::RouterService,
     rpc_sender_impl::{
-        reqwest_client, HttpServiceOptionalRetry, RpcClientSender, SolanaClientRequest,
-        SolanaClientResponse,
+        http_service_with_headers, http_service_with_redactor, HttpServiceOptionalRetry,
+        RpcClientSender, SolanaClientRequest, SolanaClientResponse,
     },
-    HttpRequestLayer, ParseResponseBodyLayer,
+    slot_aware::SlotAwareService,
+    Endpoint,
 };
+#[cfg(feature = "test-utils")]
+use super::{cassette::ReplayService, mock::MockService};
 
 pub trait ServiceBuilderExt<L> {
     fn http(self, url: Url) -> HttpClientBuilder<L>;
+    /// An HTTP client over several RPC nodes, see [FailoverService].
+    /// The first URL is the primary.
+    fn failover(self, urls: Vec<Url>) -> FailoverClientBuilder<L>;
+    /// An HTTP client that spreads requests over several RPC nodes, see [BalanceService].
+    /// Defaults to round-robin.
+    fn balance(self, urls: Vec<Url>) -> BalanceClientBuilder<L>;
+    /// An HTTP client that sends slow requests to a second RPC node as well, see [HedgeService].
+    fn hedge(self, primary: Url, secondary: Url) -> HedgeClientBuilder<L>;
+    /// An HTTP client that checks that `quorum` RPC nodes agree on responses, see [QuorumService].
+    fn quorum(self, urls: Vec<Url>, quorum: usize) -> QuorumClientBuilder<L>;
+    /// An HTTP client that only uses RPC nodes that are up to date, see [SlotAwareService].
+    fn slot_aware(self, urls: Vec<Url>) -> SlotAwareClientBuilder<L>;
+    /// Dispatch requests to different services by method, see [RouterService].
+    /// Methods without a route go to `default`.
+    fn router<S>(self, default: S) -> RouterClientBuilder<L>
+    where
+        S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+        S::Future: Send + 'static;
+    /// Serve the responses of a recorded cassette, see [ReplayService].
+    #[cfg(feature = "test-utils")]
+    fn replay(self, replay: ReplayService) -> ReplayClientBuilder<L>;
+    /// Answer requests with typed handlers, see [MockService].
+    #[cfg(feature = "test-utils")]
+    fn mock(self, mock: MockService) -> MockClientBuilder<L>;
     fn with_fn<S, F>(self, f: S) -> FnClientBuilder<L, S>
     where
         S: FnMut(SolanaClientRequest) -> F + Send + 'static,
@@ -33,6 +69,104 @@ impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
             retry_429: 5,
             url,
             commitment: None,
+            redactor: Default::default(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    fn failover(self, urls: Vec<Url>) -> FailoverClientBuilder<L> {
+        FailoverClientBuilder {
+            service_builder: self,
+            retry_429: 5,
+            urls,
+            commitment: None,
+            redactor: Default::default(),
+            on_served: None,
+            health_check_interval: None,
+        }
+    }
+
+    fn balance(self, urls: Vec<Url>) -> BalanceClientBuilder<L> {
+        BalanceClientBuilder {
+            service_builder: self,
+            retry_429: 5,
+            urls,
+            strategy: BalanceStrategy::RoundRobin,
+            commitment: None,
+            redactor: Default::default(),
+        }
+    }
+
+    fn hedge(self, primary: Url, secondary: Url) -> HedgeClientBuilder<L> {
+        HedgeClientBuilder {
+            service_builder: self,
+            retry_429: 5,
+            primary,
+            secondary,
+            methods: None,
+            hedge_non_idempotent: false,
+            percentile: None,
+            min_samples: None,
+            commitment: None,
+            redactor: Default::default(),
+        }
+    }
+
+    fn quorum(self, urls: Vec<Url>, quorum: usize) -> QuorumClientBuilder<L> {
+        QuorumClientBuilder {
+            service_builder: self,
+            retry_429: 5,
+            urls,
+            quorum,
+            methods: None,
+            slot_agreement: SlotAgreement::Any,
+            commitment: None,
+            redactor: Default::default(),
+        }
+    }
+
+    fn slot_aware(self, urls: Vec<Url>) -> SlotAwareClientBuilder<L> {
+        SlotAwareClientBuilder {
+            service_builder: self,
+            retry_429: 5,
+            urls,
+            max_slot_lag: 10,
+            poll_interval: None,
+            commitment: None,
+            redactor: Default::default(),
+        }
+    }
+
+    fn router<S>(self, default: S) -> RouterClientBuilder<L>
+    where
+        S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        RouterClientBuilder {
+            service_builder: self,
+            router: RouterService::new(default),
+            commitment: None,
+            url: None,
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn replay(self, replay: ReplayService) -> ReplayClientBuilder<L> {
+        ReplayClientBuilder {
+            service_builder: self,
+            replay,
+            commitment: None,
+            mock_url: None,
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn mock(self, mock: MockService) -> MockClientBuilder<L> {
+        MockClientBuilder {
+            service_builder: self,
+            mock,
+            commitment: None,
+            mock_url: None,
         }
     }
 
@@ -55,6 +189,8 @@ pub struct HttpClientBuilder<L> {
     retry_429: usize,
     url: Url,
     commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+    headers: HeaderMap,
 }
 
 impl<L, S> HttpClientBuilder<L>
@@ -73,22 +209,583 @@ where
         self
     }
 
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Send this header with every request, e.g. an API key.
+    /// Its value is masked in logs and errors if the [Redactor] says so.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
     pub fn build_rpc_client(self) -> RpcClient {
         let Self {
             service_builder,
             retry_429,
             url,
             commitment,
+            redactor,
+            headers,
         } = self;
-        let retry_layer =
-            (retry_429 > 0).then(|| RetryLayer::new(TooManyRequestsRetry::new(retry_429)));
         let url_str = url.to_string();
-        let service = service_builder
-            .layer(ParseResponseBodyLayer)
-            .layer(HttpRequestLayer::new(url))
-            .option_layer(retry_layer)
-            .service(reqwest_client());
-        RpcClientSender::new_with_service(url_str, service).into_rpc_client(commitment)
+        let service = service_builder.service(http_service_with_headers(
+            url,
+            retry_429,
+            &redactor,
+            headers.clone(),
+        ));
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .with_headers(headers)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct FailoverClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    retry_429: usize,
+    urls: Vec<Url>,
+    commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+    on_served: Option<OnServed>,
+    health_check_interval: Option<Duration>,
+}
+
+impl<L, S> FailoverClientBuilder<L>
+where
+    L: Layer<FailoverService<HttpServiceOptionalRetry>, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    /// 429 retries happen per endpoint, before failing over to the next one.
+    pub fn retry_429(mut self, n_times: usize) -> Self {
+        self.retry_429 = n_times;
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Register a callback that learns which endpoint served each request.
+    pub fn on_served(mut self, f: impl Fn(&RpcRequest, &Url) + Send + Sync + 'static) -> Self {
+        self.on_served = Some(Arc::new(f));
+        self
+    }
+
+    /// Check the health of every endpoint in the background, see [HealthChecker].
+    /// The client must then be built from within a tokio runtime.
+    pub fn health_check(mut self, interval: Duration) -> Self {
+        self.health_check_interval = Some(interval);
+        self
+    }
+
+    /// Panics if no URLs were given.
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            retry_429,
+            urls,
+            commitment,
+            on_served,
+            health_check_interval,
+            redactor,
+        } = self;
+        let url_str = urls.first().map(Url::to_string).unwrap_or_default();
+        let endpoints: Vec<_> = urls
+            .into_iter()
+            .map(|url| {
+                Endpoint::new(
+                    url.clone(),
+                    http_service_with_redactor(url, retry_429, &redactor),
+                )
+                .with_redactor(&redactor)
+            })
+            .collect();
+        if let Some(interval) = health_check_interval {
+            // The health checker stops on its own once the client is dropped.
+            drop(
+                HealthChecker::new(endpoints.clone())
+                    .interval(interval)
+                    .spawn(),
+            );
+        }
+        let mut failover = FailoverService::new(endpoints);
+        if let Some(on_served) = on_served {
+            failover = failover.on_served(move |request, url| on_served(request, url));
+        }
+        let service = service_builder.service(failover);
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct BalanceClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    retry_429: usize,
+    urls: Vec<Url>,
+    strategy: BalanceStrategy,
+    commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+}
+
+impl<L, S> BalanceClientBuilder<L>
+where
+    L: Layer<BalanceService<HttpServiceOptionalRetry>, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn retry_429(mut self, n_times: usize) -> Self {
+        self.retry_429 = n_times;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: BalanceStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Panics if no URLs were given, or if the strategy doesn't fit the number of URLs.
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            retry_429,
+            urls,
+            strategy,
+            commitment,
+            redactor,
+        } = self;
+        let url_str = urls.first().map(Url::to_string).unwrap_or_default();
+        let services = urls
+            .into_iter()
+            .map(|url| http_service_with_redactor(url, retry_429, &redactor))
+            .collect();
+        let service = service_builder.service(BalanceService::new(services, strategy));
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct HedgeClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    retry_429: usize,
+    primary: Url,
+    secondary: Url,
+    methods: Option<Vec<RpcRequest>>,
+    hedge_non_idempotent: bool,
+    percentile: Option<f64>,
+    min_samples: Option<usize>,
+    commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+}
+
+impl<L, S> HedgeClientBuilder<L>
+where
+    L: Layer<HedgeService<HttpServiceOptionalRetry>, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn retry_429(mut self, n_times: usize) -> Self {
+        self.retry_429 = n_times;
+        self
+    }
+
+    /// See [HedgeService::methods].
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Some(methods.into_iter().collect());
+        self
+    }
+
+    /// See [HedgeService::hedge_non_idempotent].
+    pub fn hedge_non_idempotent(mut self, hedge_non_idempotent: bool) -> Self {
+        self.hedge_non_idempotent = hedge_non_idempotent;
+        self
+    }
+
+    /// See [HedgeService::percentile].
+    pub fn percentile(mut self, percentile: f64) -> Self {
+        self.percentile = Some(percentile);
+        self
+    }
+
+    /// See [HedgeService::min_samples].
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = Some(min_samples);
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            retry_429,
+            primary,
+            secondary,
+            methods,
+            hedge_non_idempotent,
+            percentile,
+            min_samples,
+            commitment,
+            redactor,
+        } = self;
+        let url_str = primary.to_string();
+        let mut hedge = HedgeService::new(
+            Endpoint::new(
+                primary.clone(),
+                http_service_with_redactor(primary, retry_429, &redactor),
+            )
+            .with_redactor(&redactor),
+            Endpoint::new(
+                secondary.clone(),
+                http_service_with_redactor(secondary, retry_429, &redactor),
+            )
+            .with_redactor(&redactor),
+        )
+        .hedge_non_idempotent(hedge_non_idempotent);
+        if let Some(methods) = methods {
+            hedge = hedge.methods(methods);
+        }
+        if let Some(percentile) = percentile {
+            hedge = hedge.percentile(percentile);
+        }
+        if let Some(min_samples) = min_samples {
+            hedge = hedge.min_samples(min_samples);
+        }
+        let service = service_builder.service(hedge);
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct QuorumClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    retry_429: usize,
+    urls: Vec<Url>,
+    quorum: usize,
+    methods: Option<Vec<RpcRequest>>,
+    slot_agreement: SlotAgreement,
+    commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+}
+
+impl<L, S> QuorumClientBuilder<L>
+where
+    L: Layer<QuorumService<HttpServiceOptionalRetry>, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn retry_429(mut self, n_times: usize) -> Self {
+        self.retry_429 = n_times;
+        self
+    }
+
+    /// See [QuorumService::methods].
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Some(methods.into_iter().collect());
+        self
+    }
+
+    /// See [QuorumService::require_same_slot].
+    pub fn require_same_slot(self, require_same_slot: bool) -> Self {
+        self.slot_agreement(match require_same_slot {
+            true => SlotAgreement::Same,
+            false => SlotAgreement::Any,
+        })
+    }
+
+    /// See [QuorumService::slot_agreement].
+    pub fn slot_agreement(mut self, slot_agreement: SlotAgreement) -> Self {
+        self.slot_agreement = slot_agreement;
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Panics unless the quorum is between 1 and the number of URLs.
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            retry_429,
+            urls,
+            quorum,
+            methods,
+            slot_agreement,
+            commitment,
+            redactor,
+        } = self;
+        let url_str = urls.first().map(Url::to_string).unwrap_or_default();
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                Endpoint::new(
+                    url.clone(),
+                    http_service_with_redactor(url, retry_429, &redactor),
+                )
+                .with_redactor(&redactor)
+            })
+            .collect();
+        let mut quorum = QuorumService::new(endpoints, quorum).slot_agreement(slot_agreement);
+        if let Some(methods) = methods {
+            quorum = quorum.methods(methods);
+        }
+        let service = service_builder.service(quorum);
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct SlotAwareClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    retry_429: usize,
+    urls: Vec<Url>,
+    max_slot_lag: u64,
+    poll_interval: Option<Duration>,
+    commitment: Option<CommitmentConfig>,
+    redactor: Redactor,
+}
+
+impl<L, S> SlotAwareClientBuilder<L>
+where
+    L: Layer<SlotAwareService<HttpServiceOptionalRetry>, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn retry_429(mut self, n_times: usize) -> Self {
+        self.retry_429 = n_times;
+        self
+    }
+
+    /// How many slots an endpoint may fall behind the most up-to-date one. Defaults to 10.
+    pub fn max_slot_lag(mut self, max_slot_lag: u64) -> Self {
+        self.max_slot_lag = max_slot_lag;
+        self
+    }
+
+    /// Poll `getSlot` on every endpoint in the background.
+    /// The client must then be built from within a tokio runtime.
+    pub fn poll_slots(mut self, interval: Duration) -> Self {
+        self.poll_interval = Some(interval);
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// Configure which secrets in the URLs are masked in logs, stats and errors, see [Redactor].
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// Panics if no URLs were given.
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            retry_429,
+            urls,
+            max_slot_lag,
+            poll_interval,
+            commitment,
+            redactor,
+        } = self;
+        let url_str = urls.first().map(Url::to_string).unwrap_or_default();
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                Endpoint::new(
+                    url.clone(),
+                    http_service_with_redactor(url, retry_429, &redactor),
+                )
+                .with_redactor(&redactor)
+            })
+            .collect();
+        let slot_aware = SlotAwareService::new(endpoints, max_slot_lag);
+        if let Some(interval) = poll_interval {
+            // The poller stops on its own once the client is dropped.
+            drop(slot_aware.spawn_slot_poller(interval));
+        }
+        let service = service_builder.service(slot_aware);
+        RpcClientSender::new_with_service(url_str, service)
+            .with_redactor(redactor)
+            .into_rpc_client(commitment)
+    }
+}
+
+pub struct RouterClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    router: RouterService,
+    commitment: Option<CommitmentConfig>,
+    url: Option<String>,
+}
+
+impl<L> RouterClientBuilder<L> {
+    /// Send the given methods to `service` rather than the default.
+    pub fn route<S>(mut self, methods: impl IntoIterator<Item = RpcRequest>, service: S) -> Self
+    where
+        S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        self.router = self.router.route(methods, service);
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    /// The URL reported by `RpcClient::url`, since there is no single one to pick.
+    pub fn url(mut self, url: String) -> Self {
+        self.url = Some(url);
+        self
+    }
+}
+
+impl<L, S> RouterClientBuilder<L>
+where
+    L: Layer<RouterService, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            router,
+            commitment,
+            url,
+        } = self;
+        let service = service_builder.service(router);
+        RpcClientSender::new_with_service(url.unwrap_or_default(), service)
+            .into_rpc_client(commitment)
+    }
+}
+
+#[cfg(feature = "test-utils")]
+pub struct ReplayClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    replay: ReplayService,
+    commitment: Option<CommitmentConfig>,
+    mock_url: Option<String>,
+}
+
+#[cfg(feature = "test-utils")]
+impl<L> ReplayClientBuilder<L> {
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    pub fn mock_url(mut self, mock_url: String) -> Self {
+        self.mock_url = Some(mock_url);
+        self
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl<L, S> ReplayClientBuilder<L>
+where
+    L: Layer<ReplayService, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            replay,
+            commitment,
+            mock_url,
+        } = self;
+        let service = service_builder.service(replay);
+        RpcClientSender::new_with_service(mock_url.unwrap_or_default(), service)
+            .into_rpc_client(commitment)
+    }
+}
+
+#[cfg(feature = "test-utils")]
+pub struct MockClientBuilder<L> {
+    service_builder: ServiceBuilder<L>,
+    mock: MockService,
+    commitment: Option<CommitmentConfig>,
+    mock_url: Option<String>,
+}
+
+#[cfg(feature = "test-utils")]
+impl<L> MockClientBuilder<L> {
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    pub fn mock_url(mut self, mock_url: String) -> Self {
+        self.mock_url = Some(mock_url);
+        self
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl<L, S> MockClientBuilder<L>
+where
+    L: Layer<MockService, Service = S>,
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + Sync + 'static,
+    S::Future: Send + 'static,
+{
+    pub fn build_rpc_client(self) -> RpcClient {
+        let Self {
+            service_builder,
+            mock,
+            commitment,
+            mock_url,
+        } = self;
+        let service = service_builder.service(mock);
+        RpcClientSender::new_with_service(mock_url.unwrap_or_default(), service)
+            .into_rpc_client(commitment)
     }
 }
 
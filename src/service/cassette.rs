@@ -0,0 +1,279 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_client::{
+    rpc_custom_error::NodeUnhealthyErrorData,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+};
+use tower::{BoxError, Service};
+
+use super::{
+    errors::rpc_error, parse_response_body::parse_response_errors,
+    rpc_sender_impl::SolanaClientRequest,
+};
+
+/// One request and its response, as a line of a cassette file.
+/// Written by [RecordLayer](crate::middleware::RecordLayer) and served by [ReplayService].
+///
+/// Looks like `{"method":"getBalance","params":[...],"result":{...}}`,
+/// with an `"error"` object instead of `"result"` for failed requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    pub method: String,
+    pub params: Value,
+    #[serde(flatten)]
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordedResponse {
+    Result(Value),
+    Error(RecordedError),
+}
+
+/// A JSON-RPC error object, or only a message for other errors, e.g. transport errors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedError {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i64>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub data: Value,
+}
+
+impl CassetteEntry {
+    pub fn new(request: &SolanaClientRequest, response: &Result<Value, BoxError>) -> Self {
+        Self {
+            method: request.0.to_string(),
+            params: request.1.clone(),
+            response: match response {
+                Ok(value) => RecordedResponse::Result(value.clone()),
+                Err(e) => RecordedResponse::Error(RecordedError::new(e)),
+            },
+        }
+    }
+
+    /// The response as it was returned by the recorded service.
+    /// JSON-RPC errors come back as the same [RpcError], other errors only keep their message.
+    pub fn response(&self) -> Result<Value, BoxError> {
+        match &self.response {
+            RecordedResponse::Result(value) => Ok(value.clone()),
+            RecordedResponse::Error(RecordedError {
+                code: Some(code),
+                message,
+                data,
+            }) => parse_response_errors(json!({
+                "error": { "code": code, "message": message, "data": data }
+            })),
+            RecordedResponse::Error(RecordedError { message, .. }) => Err(message.as_str().into()),
+        }
+    }
+}
+
+impl RecordedError {
+    fn new(err: &BoxError) -> Self {
+        match rpc_error(err) {
+            Some(RpcError::RpcResponseError {
+                code,
+                message,
+                data,
+            }) => Self {
+                code: Some(*code),
+                message: message.clone(),
+                data: match data {
+                    RpcResponseErrorData::Empty => Value::Null,
+                    RpcResponseErrorData::SendTransactionPreflightFailure(result) => {
+                        json!(result)
+                    }
+                    RpcResponseErrorData::NodeUnhealthy { num_slots_behind } => {
+                        json!(NodeUnhealthyErrorData {
+                            num_slots_behind: *num_slots_behind
+                        })
+                    }
+                },
+            },
+            _ => Self {
+                code: None,
+                message: err.to_string(),
+                data: Value::Null,
+            },
+        }
+    }
+}
+
+/// Read a cassette written by [RecordLayer](crate::middleware::RecordLayer).
+pub fn read_cassette(path: impl AsRef<Path>) -> io::Result<Vec<CassetteEntry>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for (i, line) in file.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("cassette line {}: {e}", i + 1),
+            )
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// How a request is matched against the recorded ones. The method always has to be the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchRule {
+    /// The params have to be the same.
+    Exact,
+    /// Any params match.
+    Method,
+    /// The params have to be the same, apart from these fields, given as JSON pointers,
+    /// e.g. `/1/minContextSlot` for the second param's `minContextSlot`.
+    IgnoreFields(Vec<String>),
+}
+
+impl MatchRule {
+    fn matches(&self, recorded: &Value, params: &Value) -> bool {
+        match self {
+            MatchRule::Exact => recorded == params,
+            MatchRule::Method => true,
+            MatchRule::IgnoreFields(fields) => {
+                without_fields(recorded, fields) == without_fields(params, fields)
+            }
+        }
+    }
+}
+
+fn without_fields(params: &Value, fields: &[String]) -> Value {
+    let mut params = params.clone();
+    for field in fields {
+        let Some((parent, key)) = field.rsplit_once('/') else {
+            continue;
+        };
+        let key = key.replace("~1", "/").replace("~0", "~");
+        match params.pointer_mut(parent) {
+            Some(Value::Object(object)) => {
+                object.remove(&key);
+            }
+            Some(Value::Array(array)) => {
+                if let Some(value) = key.parse().ok().and_then(|i: usize| array.get_mut(i)) {
+                    *value = Value::Null;
+                }
+            }
+            _ => {}
+        }
+    }
+    params
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    entries: Vec<CassetteEntry>,
+    served: Vec<bool>,
+}
+
+/// Serves the responses of a cassette recorded with [RecordLayer](crate::middleware::RecordLayer),
+/// for offline, deterministic tests. Use it like any other service,
+/// e.g. with [ServiceBuilderExt::replay](super::builder::ServiceBuilderExt::replay).
+///
+/// Matching entries are served in the order they were recorded, and the last one is served
+/// again once they have all been used, e.g. for polling. Requests without a matching entry
+/// fail with an error that names the method and params, and are logged as errors.
+#[derive(Debug, Clone)]
+pub struct ReplayService {
+    state: Arc<Mutex<ReplayState>>,
+    default_rule: MatchRule,
+    rules: Arc<HashMap<RpcRequest, MatchRule>>,
+}
+
+impl ReplayService {
+    /// Matches requests with [MatchRule::Exact] by default.
+    pub fn new(entries: Vec<CassetteEntry>) -> Self {
+        let served = vec![false; entries.len()];
+        Self {
+            state: Arc::new(Mutex::new(ReplayState { entries, served })),
+            default_rule: MatchRule::Exact,
+            rules: Default::default(),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(read_cassette(path)?))
+    }
+
+    /// The rule for methods without their own rule.
+    pub fn match_rule(mut self, rule: MatchRule) -> Self {
+        self.default_rule = rule;
+        self
+    }
+
+    /// The rule for requests of `method`.
+    pub fn match_rule_for(mut self, method: RpcRequest, rule: MatchRule) -> Self {
+        Arc::make_mut(&mut self.rules).insert(method, rule);
+        self
+    }
+
+    /// The entries that no request has matched yet.
+    pub fn unused_entries(&self) -> Vec<CassetteEntry> {
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .iter()
+            .zip(&state.served)
+            .filter(|(_, served)| !**served)
+            .map(|(entry, _)| entry.clone())
+            .collect()
+    }
+
+    fn find(&self, (method, params): &SolanaClientRequest) -> Option<CassetteEntry> {
+        let rule = self.rules.get(method).unwrap_or(&self.default_rule);
+        let method = method.to_string();
+        let mut state = self.state.lock().unwrap();
+        let matching: Vec<usize> = state
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.method == method && rule.matches(&entry.params, params))
+            .map(|(i, _)| i)
+            .collect();
+        let i = matching
+            .iter()
+            .copied()
+            .find(|i| !state.served[*i])
+            .or(matching.last().copied())?;
+        state.served[i] = true;
+        Some(state.entries[i].clone())
+    }
+}
+
+impl Service<SolanaClientRequest> for ReplayService {
+    type Response = Value;
+    type Error = BoxError;
+    type Future = Ready<Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: SolanaClientRequest) -> Self::Future {
+        ready(match self.find(&req) {
+            Some(entry) => entry.response(),
+            None => {
+                let message = format!("no recorded response for {} {}", req.0, req.1);
+                tracing::error!(method = %req.0, params = %req.1, "no recorded response");
+                Err(RpcError::RpcRequestError(message).into())
+            }
+        })
+    }
+}
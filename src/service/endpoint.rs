@@ -0,0 +1,145 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Instant,
+};
+
+use futures::future::BoxFuture;
+use reqwest::Url;
+use serde_json::Value;
+use tower::{BoxError, Service, ServiceExt};
+
+use super::{
+    health::EndpointHealth,
+    redact::Redactor,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+    stats_updater::StatsUpdater,
+};
+
+/// An upstream RPC node, paired with the [Service] used to reach it.
+/// Used as a building block by services that spread requests over several nodes.
+///
+/// Clones are cheap, and share the same underlying service and health state.
+pub struct Endpoint<S> {
+    url: Url,
+    redacted_url: Url,
+    service: Arc<tokio::sync::Mutex<S>>,
+    health: Arc<EndpointHealth>,
+}
+
+impl<S> Clone for Endpoint<S> {
+    fn clone(&self) -> Self {
+        Self {
+            url: self.url.clone(),
+            redacted_url: self.redacted_url.clone(),
+            service: self.service.clone(),
+            health: self.health.clone(),
+        }
+    }
+}
+
+impl<S> Endpoint<S> {
+    pub fn new(url: Url, service: S) -> Self {
+        Self {
+            redacted_url: Redactor::default().redact_url(&url),
+            url,
+            service: Arc::new(tokio::sync::Mutex::new(service)),
+            health: Default::default(),
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Configure which secrets are masked in [Endpoint::redacted_url].
+    pub fn with_redactor(mut self, redactor: &Redactor) -> Self {
+        self.redacted_url = redactor.redact_url(&self.url);
+        self
+    }
+
+    /// The URL for logs, stats and error messages.
+    pub fn redacted_url(&self) -> &Url {
+        &self.redacted_url
+    }
+
+    /// Kept up to date by a [HealthChecker](super::HealthChecker), if there is one.
+    pub fn health(&self) -> &EndpointHealth {
+        &self.health
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health.is_healthy()
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakEndpoint<S> {
+        WeakEndpoint {
+            url: self.url.clone(),
+            redacted_url: self.redacted_url.clone(),
+            service: Arc::downgrade(&self.service),
+            health: Arc::downgrade(&self.health),
+        }
+    }
+}
+
+/// An [Endpoint] that does not keep its service alive, for background tasks.
+pub(crate) struct WeakEndpoint<S> {
+    url: Url,
+    redacted_url: Url,
+    service: Weak<tokio::sync::Mutex<S>>,
+    health: Weak<EndpointHealth>,
+}
+
+impl<S> WeakEndpoint<S> {
+    pub(crate) fn upgrade(&self) -> Option<Endpoint<S>> {
+        Some(Endpoint {
+            url: self.url.clone(),
+            redacted_url: self.redacted_url.clone(),
+            service: self.service.upgrade()?,
+            health: self.health.upgrade()?,
+        })
+    }
+}
+
+impl<S> Endpoint<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    /// Wait for the service to be ready, and then call it.
+    /// The response is counted in the endpoint's stats of the request being processed, if any.
+    /// The lock on the service is released before the response is awaited,
+    /// so requests to the same endpoint can still be in flight concurrently.
+    pub fn call(&self, request: SolanaClientRequest) -> BoxFuture<'static, SolanaClientResponse> {
+        let fut = call_shared(&self.service, request);
+        let url = self.redacted_url.clone();
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = fut.await;
+            StatsUpdater::with_current(|stats| {
+                stats.add_endpoint_response(url.as_str(), start.elapsed(), &result)
+            });
+            result
+        })
+    }
+}
+
+/// Wait for a shared service to be ready, and call it, without holding the lock
+/// while the response is awaited.
+pub(crate) fn call_shared<S>(
+    service: &Arc<tokio::sync::Mutex<S>>,
+    request: SolanaClientRequest,
+) -> BoxFuture<'static, SolanaClientResponse>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let service = service.clone();
+    Box::pin(async move {
+        let fut = {
+            let mut service = service.lock().await;
+            service.ready().await?;
+            service.call(request)
+        };
+        fut.await
+    })
+}
@@ -0,0 +1,124 @@
+use reqwest::StatusCode;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY as NODE_UNHEALTHY,
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use std::error::Error;
+use tower::BoxError;
+
+use super::quorum::QuorumError;
+
+/// Iterate over an error and all of its sources.
+fn error_chain<'a>(
+    err: &'a (dyn Error + 'static),
+) -> impl Iterator<Item = &'a (dyn Error + 'static)> {
+    std::iter::successors(Some(err), |e| (*e).source())
+}
+
+/// The `RpcError`, either bare (as produced by [ParseResponseBody](super::ParseResponseBody))
+/// or wrapped in a [ClientError].
+pub fn rpc_error(err: &BoxError) -> Option<&RpcError> {
+    error_chain(err.as_ref()).find_map(|e| match e.downcast_ref::<ClientError>() {
+        Some(ClientError {
+            kind: ClientErrorKind::RpcError(rpc_error),
+            ..
+        }) => Some(rpc_error),
+        _ => e.downcast_ref::<RpcError>(),
+    })
+}
+
+/// The JSON-RPC error code of an `RpcError::RpcResponseError`.
+pub fn rpc_error_code(err: &BoxError) -> Option<i64> {
+    match rpc_error(err)? {
+        RpcError::RpcResponseError { code, .. } => Some(*code),
+        _ => None,
+    }
+}
+
+/// How far behind a node said it was, when it responded with "node is unhealthy".
+pub fn num_slots_behind(err: &BoxError) -> Option<u64> {
+    match rpc_error(err)? {
+        RpcError::RpcResponseError {
+            data: RpcResponseErrorData::NodeUnhealthy { num_slots_behind },
+            ..
+        } => *num_slots_behind,
+        _ => None,
+    }
+}
+
+/// The underlying [reqwest::Error], if the error happened at the HTTP level.
+pub fn reqwest_error(err: &BoxError) -> Option<&reqwest::Error> {
+    error_chain(err.as_ref()).find_map(|e| match e.downcast_ref::<ClientError>() {
+        Some(ClientError {
+            kind: ClientErrorKind::Reqwest(e),
+            ..
+        }) => Some(e),
+        _ => e.downcast_ref::<reqwest::Error>(),
+    })
+}
+
+/// The HTTP status code of the response, if the server answered with a non-success status.
+pub fn http_status(err: &BoxError) -> Option<StatusCode> {
+    reqwest_error(err).and_then(|e| e.status())
+}
+
+/// The underlying [std::io::Error], e.g. a connection reset.
+pub fn io_error(err: &BoxError) -> Option<&std::io::Error> {
+    error_chain(err.as_ref()).find_map(|e| match e.downcast_ref::<ClientError>() {
+        Some(ClientError {
+            kind: ClientErrorKind::Io(e),
+            ..
+        }) => Some(e),
+        _ => e.downcast_ref::<std::io::Error>(),
+    })
+}
+
+/// The [QuorumError] of a request an `RpcClient` sent through a [QuorumService](super::QuorumService),
+/// parsed back from the `ClientErrorKind::Custom` error, see [QuorumError::from_message].
+/// Errors of the service itself can be downcast to a [QuorumError] instead.
+pub fn quorum_error(err: &ClientError) -> Option<QuorumError> {
+    match (&err.kind, err.request) {
+        (ClientErrorKind::Custom(message), Some(method)) => {
+            QuorumError::from_message(method, message)
+        }
+        _ => None,
+    }
+}
+
+/// The [serde_json::Error] of a response body that isn't JSON,
+/// e.g. a proxy's HTML error page, or a response that was cut off.
+pub fn json_error(err: &BoxError) -> Option<&serde_json::Error> {
+    error_chain(err.as_ref()).find_map(|e| match e.downcast_ref::<ClientError>() {
+        Some(ClientError {
+            kind: ClientErrorKind::SerdeJson(e),
+            ..
+        }) => Some(e),
+        _ => e.downcast_ref::<serde_json::Error>(),
+    })
+}
+
+/// True if the request failed to reach the server, or the response could not be read
+/// or wasn't JSON, including I/O errors.
+/// Note that this does not include responses with a non-success HTTP status.
+pub fn is_transport_error(err: &BoxError) -> bool {
+    reqwest_error(err).is_some_and(|e| e.status().is_none())
+        || io_error(err).is_some()
+        || json_error(err).is_some()
+}
+
+/// True if the node responded with a JSON-RPC "node is unhealthy" error.
+pub fn is_node_unhealthy(err: &BoxError) -> bool {
+    rpc_error_code(err) == Some(NODE_UNHEALTHY)
+}
+
+/// True if the error says something about the health of the RPC node
+/// rather than about the request itself: transport errors, 5xx and 429 responses,
+/// and "node is unhealthy" responses.
+pub fn is_node_error(err: &BoxError) -> bool {
+    is_transport_error(err)
+        || http_status(err).is_some_and(|status| {
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        })
+        || is_node_unhealthy(err)
+}
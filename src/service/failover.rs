@@ -0,0 +1,120 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use reqwest::Url;
+use serde_json::Value;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::RpcRequest,
+};
+use tower::{BoxError, Service};
+
+use super::{
+    endpoint::Endpoint,
+    errors::is_node_error,
+    health::poll_any_healthy,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+};
+
+/// Called with the endpoint that successfully served a request.
+pub type OnServed = Arc<dyn Fn(&RpcRequest, &Url) + Send + Sync>;
+
+/// Sends every request to the first endpoint, and falls over to the next one in order
+/// whenever an endpoint fails in a way that says something about the node rather than the request.
+/// That is, transport errors, 5xx and 429 responses (after any 429 retries),
+/// and JSON-RPC "node is unhealthy" errors.
+///
+/// Endpoints that a [HealthChecker](super::HealthChecker) found unhealthy are tried last,
+/// and the service isn't ready while none is healthy.
+///
+/// Any other error (e.g. a transaction failing preflight) is returned as is.
+/// If every endpoint fails, the error from the last one is returned.
+pub struct FailoverService<S> {
+    endpoints: Arc<Vec<Endpoint<S>>>,
+    on_served: Option<OnServed>,
+}
+
+impl<S> Clone for FailoverService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            endpoints: self.endpoints.clone(),
+            on_served: self.on_served.clone(),
+        }
+    }
+}
+
+impl<S> FailoverService<S> {
+    /// Endpoints are tried in the order given. Panics if `endpoints` is empty.
+    pub fn new(endpoints: Vec<Endpoint<S>>) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "FailoverService requires at least one endpoint"
+        );
+        Self {
+            endpoints: Arc::new(endpoints),
+            on_served: None,
+        }
+    }
+
+    /// Register a callback that learns which endpoint served each request.
+    pub fn on_served(mut self, f: impl Fn(&RpcRequest, &Url) + Send + Sync + 'static) -> Self {
+        self.on_served = Some(Arc::new(f));
+        self
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint<S>] {
+        &self.endpoints
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for FailoverService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    /// Ready while any endpoint is healthy. The readiness of the endpoint itself
+    /// is checked as each one is tried.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        poll_any_healthy(self.endpoints.iter(), cx)
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let endpoints = self.endpoints.clone();
+        let on_served = self.on_served.clone();
+        Box::pin(async move {
+            let mut last_error = None;
+            // Unhealthy endpoints are only tried once all the healthy ones have failed.
+            let (healthy, unhealthy): (Vec<_>, Vec<_>) =
+                endpoints.iter().partition(|e| e.is_healthy());
+            for endpoint in healthy.into_iter().chain(unhealthy) {
+                match endpoint.call(request.clone()).await {
+                    Ok(value) => {
+                        tracing::debug!(method = %request.0, endpoint = %endpoint.redacted_url(), "request served");
+                        if let Some(on_served) = &on_served {
+                            on_served(&request.0, endpoint.url());
+                        }
+                        return Ok(value);
+                    }
+                    Err(e) if is_node_error(&e) => {
+                        tracing::warn!(method = %request.0, endpoint = %endpoint.redacted_url(), err = ?e, "endpoint failed");
+                        last_error = Some(e);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(last_error.unwrap_or_else(|| {
+                Box::new(ClientError::new_with_request(
+                    ClientErrorKind::Custom("No RPC endpoints available".to_string()),
+                    request.0,
+                ))
+            }))
+        })
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use futures::future::{ready, Ready};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::{Response, RpcBlockhash, RpcKeyedAccount, RpcResponseContext},
+};
+use solana_sdk::{
+    account::Account,
+    hash::{hash, hashv, Hash},
+    pubkey::Pubkey,
+    signature::Signature,
+    system_program,
+    transaction::VersionedTransaction,
+};
+use tower::{BoxError, Service};
+
+use super::{mock::METHOD_NOT_FOUND, rpc_sender_impl::SolanaClientRequest};
+
+/// The JSON-RPC error code for malformed params.
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// How many blocks a blockhash stays valid for, as on a real cluster.
+const MAX_PROCESSING_AGE: u64 = 150;
+
+#[derive(Debug)]
+struct LedgerState {
+    accounts: BTreeMap<Pubkey, Account>,
+    slot: u64,
+    block_height: u64,
+    blockhash: Hash,
+    transactions: Vec<VersionedTransaction>,
+    signatures: HashMap<Signature, u64>,
+}
+
+/// A stateful, in-memory stand-in for an RPC node, for tests that need consistent answers
+/// across requests without running a validator. It holds accounts, a slot counter
+/// and the latest blockhash, and answers:
+/// - `GetBalance`, `GetAccountInfo`, `GetMultipleAccounts`
+/// - `GetProgramAccounts`, with `memcmp` and `dataSize` filters; other filters match nothing
+/// - `GetSlot`, `GetBlockHeight`, `GetLatestBlockhash`
+/// - `SendTransaction`, which records the decoded transaction without executing it,
+///   and `GetSignatureStatuses`, which reports recorded transactions as finalized
+///
+/// Other methods fail with a "Method not found" JSON-RPC error.
+/// Clones share the ledger, so tests can keep one to set up accounts and inspect transactions.
+#[derive(Debug, Clone)]
+pub struct FakeLedger {
+    state: Arc<RwLock<LedgerState>>,
+}
+
+impl Default for FakeLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeLedger {
+    /// An empty ledger at slot 1.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(RwLock::new(LedgerState {
+                accounts: BTreeMap::new(),
+                slot: 1,
+                block_height: 1,
+                blockhash: hash(b"fake ledger"),
+                transactions: Vec::new(),
+                signatures: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.state.write().unwrap().accounts.insert(pubkey, account);
+    }
+
+    /// Set the lamports of `pubkey`, creating a system account if there is none.
+    pub fn set_balance(&self, pubkey: Pubkey, lamports: u64) {
+        let mut state = self.state.write().unwrap();
+        let account = state.accounts.entry(pubkey).or_insert_with(|| Account {
+            owner: system_program::id(),
+            ..Default::default()
+        });
+        account.lamports = lamports;
+    }
+
+    pub fn remove_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.state.write().unwrap().accounts.remove(pubkey)
+    }
+
+    pub fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.state.read().unwrap().accounts.get(pubkey).cloned()
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.state.read().unwrap().slot
+    }
+
+    pub fn latest_blockhash(&self) -> Hash {
+        self.state.read().unwrap().blockhash
+    }
+
+    /// Move on by `slots` slots, producing a block with a new blockhash in each.
+    pub fn advance_slots(&self, slots: u64) {
+        let mut state = self.state.write().unwrap();
+        for _ in 0..slots {
+            state.slot += 1;
+            state.block_height += 1;
+            state.blockhash = hashv(&[state.blockhash.as_ref()]);
+        }
+    }
+
+    /// The transactions sent so far, in order.
+    pub fn transactions(&self) -> Vec<VersionedTransaction> {
+        self.state.read().unwrap().transactions.clone()
+    }
+
+    fn context(&self, state: &LedgerState) -> RpcResponseContext {
+        RpcResponseContext {
+            slot: state.slot,
+            api_version: None,
+        }
+    }
+
+    fn handle(&self, method: RpcRequest, params: &Value) -> Result<Value, BoxError> {
+        match method {
+            RpcRequest::GetBalance => {
+                let pubkey = pubkey_param(params, 0)?;
+                let state = self.state.read().unwrap();
+                let lamports = state.accounts.get(&pubkey).map_or(0, |a| a.lamports);
+                with_context(self.context(&state), lamports)
+            }
+            RpcRequest::GetAccountInfo => {
+                let pubkey = pubkey_param(params, 0)?;
+                let config: RpcAccountInfoConfig = param(params, 1)?.unwrap_or_default();
+                let state = self.state.read().unwrap();
+                let account = state
+                    .accounts
+                    .get(&pubkey)
+                    .map(|account| encode(&pubkey, account, &config));
+                with_context(self.context(&state), account)
+            }
+            RpcRequest::GetMultipleAccounts => {
+                let pubkeys: Vec<String> = required_param(params, 0)?;
+                let config: RpcAccountInfoConfig = param(params, 1)?.unwrap_or_default();
+                let pubkeys = pubkeys
+                    .iter()
+                    .map(|pubkey| parse_pubkey(pubkey))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let state = self.state.read().unwrap();
+                let accounts: Vec<_> = pubkeys
+                    .iter()
+                    .map(|pubkey| {
+                        state
+                            .accounts
+                            .get(pubkey)
+                            .map(|account| encode(pubkey, account, &config))
+                    })
+                    .collect();
+                with_context(self.context(&state), accounts)
+            }
+            RpcRequest::GetProgramAccounts => {
+                let program_id = pubkey_param(params, 0)?;
+                let config: RpcProgramAccountsConfig = param(params, 1)?.unwrap_or_default();
+                let filters = config.filters.unwrap_or_default();
+                let state = self.state.read().unwrap();
+                let accounts: Vec<_> = state
+                    .accounts
+                    .iter()
+                    .filter(|(_, account)| account.owner == program_id)
+                    .filter(|(_, account)| {
+                        filters
+                            .iter()
+                            .all(|filter| filter_allows(filter, &account.data))
+                    })
+                    .map(|(pubkey, account)| RpcKeyedAccount {
+                        pubkey: pubkey.to_string(),
+                        account: encode(pubkey, account, &config.account_config),
+                    })
+                    .collect();
+                match config.with_context {
+                    Some(true) => with_context(self.context(&state), accounts),
+                    _ => Ok(json!(accounts)),
+                }
+            }
+            RpcRequest::GetSlot => Ok(json!(self.state.read().unwrap().slot)),
+            RpcRequest::GetBlockHeight => Ok(json!(self.state.read().unwrap().block_height)),
+            RpcRequest::GetLatestBlockhash => {
+                let state = self.state.read().unwrap();
+                let blockhash = RpcBlockhash {
+                    blockhash: state.blockhash.to_string(),
+                    last_valid_block_height: state.block_height + MAX_PROCESSING_AGE,
+                };
+                with_context(self.context(&state), blockhash)
+            }
+            RpcRequest::SendTransaction => {
+                let encoded: String = required_param(params, 0)?;
+                let config: SendTransactionConfig = param(params, 1)?.unwrap_or_default();
+                let bytes = match config.encoding.as_deref() {
+                    None | Some("base58") => bs58::decode(&encoded).into_vec().ok(),
+                    Some("base64") => BASE64_STANDARD.decode(&encoded).ok(),
+                    Some(_) => None,
+                }
+                .ok_or_else(|| invalid_params("invalid transaction encoding"))?;
+                let transaction: VersionedTransaction = bincode::deserialize(&bytes)
+                    .map_err(|e| invalid_params(format!("invalid transaction: {e}")))?;
+                let signature = *transaction
+                    .signatures
+                    .first()
+                    .ok_or_else(|| invalid_params("transaction has no signatures"))?;
+                let mut state = self.state.write().unwrap();
+                let slot = state.slot;
+                state.signatures.insert(signature, slot);
+                state.transactions.push(transaction);
+                Ok(json!(signature.to_string()))
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let signatures: Vec<String> = required_param(params, 0)?;
+                let state = self.state.read().unwrap();
+                let statuses: Vec<_> = signatures
+                    .iter()
+                    .map(|signature| {
+                        let signature = Signature::from_str(signature).ok()?;
+                        let slot = state.signatures.get(&signature)?;
+                        Some(json!({
+                            "slot": slot,
+                            "confirmations": null,
+                            "err": null,
+                            "status": { "Ok": null },
+                            "confirmationStatus": "finalized",
+                        }))
+                    })
+                    .collect();
+                with_context(self.context(&state), statuses)
+            }
+            _ => Err(RpcError::RpcResponseError {
+                code: METHOD_NOT_FOUND,
+                message: "Method not found".to_string(),
+                data: RpcResponseErrorData::Empty,
+            }
+            .into()),
+        }
+    }
+}
+
+impl Service<SolanaClientRequest> for FakeLedger {
+    type Response = Value;
+    type Error = BoxError;
+    type Future = Ready<Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, (method, params): SolanaClientRequest) -> Self::Future {
+        ready(self.handle(method, &params))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SendTransactionConfig {
+    encoding: Option<String>,
+}
+
+fn with_context<T: serde::Serialize>(
+    context: RpcResponseContext,
+    value: T,
+) -> Result<Value, BoxError> {
+    Ok(json!(Response { context, value }))
+}
+
+/// Same as a node, accounts are base58 encoded unless another encoding is asked for.
+fn encode(pubkey: &Pubkey, account: &Account, config: &RpcAccountInfoConfig) -> UiAccount {
+    let encoding = config.encoding.unwrap_or(UiAccountEncoding::Binary);
+    UiAccount::encode(pubkey, account, encoding, None, config.data_slice)
+}
+
+fn filter_allows(filter: &RpcFilterType, data: &[u8]) -> bool {
+    match filter {
+        RpcFilterType::DataSize(size) => data.len() as u64 == *size,
+        RpcFilterType::Memcmp(memcmp) => memcmp.bytes_match(data),
+        RpcFilterType::TokenAccountState => false,
+    }
+}
+
+fn invalid_params(message: impl std::fmt::Display) -> BoxError {
+    RpcError::RpcResponseError {
+        code: INVALID_PARAMS,
+        message: format!("Invalid params: {message}"),
+        data: RpcResponseErrorData::Empty,
+    }
+    .into()
+}
+
+fn param<T: DeserializeOwned>(params: &Value, i: usize) -> Result<Option<T>, BoxError> {
+    match params.get(i) {
+        None | Some(Value::Null) => Ok(None),
+        Some(param) => serde_json::from_value(param.clone())
+            .map(Some)
+            .map_err(invalid_params),
+    }
+}
+
+fn required_param<T: DeserializeOwned>(params: &Value, i: usize) -> Result<T, BoxError> {
+    param(params, i)?.ok_or_else(|| invalid_params(format!("missing param {i}")))
+}
+
+fn parse_pubkey(pubkey: &str) -> Result<Pubkey, BoxError> {
+    Pubkey::from_str(pubkey).map_err(|_| invalid_params("invalid pubkey"))
+}
+
+fn pubkey_param(params: &Value, i: usize) -> Result<Pubkey, BoxError> {
+    parse_pubkey(&required_param::<String>(params, i)?)
+}
@@ -0,0 +1,325 @@
+use std::{
+    sync::Mutex,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use futures::future::join_all;
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use tower::{BoxError, Service};
+
+use super::{
+    endpoint::{Endpoint, WeakEndpoint},
+    errors::num_slots_behind,
+    rpc_sender_impl::SolanaClientRequest,
+};
+
+/// The health of an endpoint, as last seen by a [HealthChecker].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Health {
+    /// Not checked yet.
+    Unknown,
+    Healthy,
+    /// The node said it is behind, or could not be reached.
+    /// It stays unhealthy until a check succeeds after its ejection backoff has passed.
+    Unhealthy {
+        num_slots_behind: Option<u64>,
+    },
+}
+
+#[derive(Debug)]
+struct HealthState {
+    health: Health,
+    slot: Option<u64>,
+    consecutive_failures: u32,
+    ejected_until: Option<Instant>,
+    /// Tasks waiting for the endpoint to be healthy again.
+    wakers: Vec<Waker>,
+    /// How many [HealthChecker]s could re-admit the endpoint.
+    checkers: usize,
+}
+
+/// Health state shared by all clones of an [Endpoint].
+#[derive(Debug)]
+pub struct EndpointHealth {
+    state: Mutex<HealthState>,
+}
+
+impl Default for EndpointHealth {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(HealthState {
+                health: Health::Unknown,
+                slot: None,
+                consecutive_failures: 0,
+                ejected_until: None,
+                wakers: Vec::new(),
+                checkers: 0,
+            }),
+        }
+    }
+}
+
+impl EndpointHealth {
+    pub fn health(&self) -> Health {
+        self.state.lock().unwrap().health.clone()
+    }
+
+    /// Endpoints that have not been checked yet count as healthy.
+    pub fn is_healthy(&self) -> bool {
+        !matches!(self.health(), Health::Unhealthy { .. })
+    }
+
+    /// Ready once the endpoint is healthy, waking the task when a [HealthChecker] re-admits it.
+    pub fn poll_healthy(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(state.health, Health::Unhealthy { .. }) {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Whether a [HealthChecker] of the endpoint is alive, or running in a spawned task.
+    pub fn is_checked(&self) -> bool {
+        self.state.lock().unwrap().checkers > 0
+    }
+
+    /// The latest slot seen by a [HealthChecker] with `check_slot` enabled.
+    pub fn slot(&self) -> Option<u64> {
+        self.state.lock().unwrap().slot
+    }
+
+    fn record_success(&self, slot: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        if slot.is_some() {
+            state.slot = slot;
+        }
+        if state.ejected_until.is_some_and(|t| Instant::now() < t) {
+            return;
+        }
+        state.health = Health::Healthy;
+        state.consecutive_failures = 0;
+        state.ejected_until = None;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn add_checker(&self) {
+        self.state.lock().unwrap().checkers += 1;
+    }
+
+    /// Once no checker is left, tasks waiting for the endpoint won't be woken by one,
+    /// so wake them to give up.
+    fn remove_checker(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.checkers -= 1;
+        if state.checkers == 0 {
+            for waker in state.wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Eject the endpoint, for twice as long as the previous time if it failed again.
+    fn record_failure(
+        &self,
+        num_slots_behind: Option<u64>,
+        backoff: Duration,
+        max_backoff: Duration,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let backoff = backoff
+            .saturating_mul(2u32.saturating_pow(state.consecutive_failures))
+            .min(max_backoff);
+        state.health = Health::Unhealthy { num_slots_behind };
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        state.ejected_until = Some(Instant::now() + backoff);
+    }
+}
+
+/// For the `poll_ready` of services over several endpoints: ready while any endpoint is
+/// healthy, and otherwise pending until a [HealthChecker] re-admits one.
+/// Fails if there's no checker left that could.
+pub(crate) fn poll_any_healthy<'a, S: 'a>(
+    endpoints: impl IntoIterator<Item = &'a Endpoint<S>>,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), BoxError>> {
+    let mut checked = false;
+    for endpoint in endpoints {
+        let health = endpoint.health();
+        if health.poll_healthy(cx).is_ready() {
+            return Poll::Ready(Ok(()));
+        }
+        checked |= health.is_checked();
+    }
+    match checked {
+        true => Poll::Pending,
+        false => Poll::Ready(Err(
+            "No endpoint is healthy, and no HealthChecker is left to re-admit one".into(),
+        )),
+    }
+}
+
+/// Periodically calls `getHealth`, and optionally `getSlot`, on each endpoint,
+/// and records the result in the endpoint's [EndpointHealth].
+/// Services over several endpoints (e.g. [FailoverService](super::FailoverService))
+/// then avoid unhealthy ones while there are healthy ones left, and aren't ready
+/// (their `poll_ready` is pending) while none is healthy. Once the checker is dropped,
+/// or its spawned task ends, they fail instead, since nothing would re-admit an endpoint.
+///
+/// An endpoint that fails a check is ejected, and only re-admitted once a check succeeds
+/// after the ejection backoff. The backoff doubles each time the endpoint fails again,
+/// up to `max_backoff`.
+pub struct HealthChecker<S> {
+    endpoints: Vec<Endpoint<S>>,
+    interval: Duration,
+    check_slot: bool,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<S> HealthChecker<S> {
+    /// Checks every 10 seconds, and ejects for 5 seconds up to 5 minutes.
+    /// `endpoints` should be clones of those given to the services that consult their health.
+    pub fn new(endpoints: Vec<Endpoint<S>>) -> Self {
+        for endpoint in &endpoints {
+            endpoint.health().add_checker();
+        }
+        Self {
+            endpoints,
+            interval: Duration::from_secs(10),
+            check_slot: false,
+            backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Also call `getSlot` at "processed" commitment, see [EndpointHealth::slot].
+    pub fn check_slot(mut self, check_slot: bool) -> Self {
+        self.check_slot = check_slot;
+        self
+    }
+
+    pub fn backoff(mut self, backoff: Duration, max_backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+impl<S> HealthChecker<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    /// Check every endpoint once.
+    pub async fn check_all(&self) {
+        join_all(
+            self.endpoints
+                .iter()
+                .map(|endpoint| check(endpoint, self.check_slot, self.backoff, self.max_backoff)),
+        )
+        .await;
+    }
+
+    /// Check every endpoint every `interval`, until the returned task is aborted
+    /// or every other clone of the endpoints is dropped. Must be called from within a tokio runtime.
+    pub fn spawn(mut self) -> tokio::task::JoinHandle<()> {
+        // The task takes over as the endpoints' checker
+        let endpoints = std::mem::take(&mut self.endpoints);
+        let checking = Checking(endpoints.iter().map(Endpoint::downgrade).collect());
+        let (interval, check_slot, backoff, max_backoff) = (
+            self.interval,
+            self.check_slot,
+            self.backoff,
+            self.max_backoff,
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let endpoints: Vec<_> = checking
+                    .0
+                    .iter()
+                    .filter_map(WeakEndpoint::upgrade)
+                    .collect();
+                if endpoints.is_empty() {
+                    break;
+                }
+                join_all(
+                    endpoints
+                        .iter()
+                        .map(|endpoint| check(endpoint, check_slot, backoff, max_backoff)),
+                )
+                .await;
+            }
+        })
+    }
+}
+
+impl<S> Drop for HealthChecker<S> {
+    fn drop(&mut self) {
+        for endpoint in &self.endpoints {
+            endpoint.health().remove_checker();
+        }
+    }
+}
+
+/// The endpoints of a spawned [HealthChecker], which stops checking them when dropped.
+struct Checking<S>(Vec<WeakEndpoint<S>>);
+
+impl<S> Drop for Checking<S> {
+    fn drop(&mut self) {
+        for endpoint in self.0.iter().filter_map(WeakEndpoint::upgrade) {
+            endpoint.health().remove_checker();
+        }
+    }
+}
+
+async fn check<S>(
+    endpoint: &Endpoint<S>,
+    check_slot: bool,
+    backoff: Duration,
+    max_backoff: Duration,
+) where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let result = async {
+        endpoint.call((RpcRequest::GetHealth, Value::Null)).await?;
+        if !check_slot {
+            return Ok(None);
+        }
+        let slot = endpoint
+            .call((RpcRequest::GetSlot, json!([{"commitment": "processed"}])))
+            .await?;
+        Ok::<_, BoxError>(slot.as_u64())
+    };
+    let was_healthy = endpoint.is_healthy();
+    match result.await {
+        Ok(slot) => {
+            endpoint.health().record_success(slot);
+            if !was_healthy && endpoint.is_healthy() {
+                tracing::info!(endpoint = %endpoint.redacted_url(), "endpoint re-admitted");
+            }
+        }
+        Err(e) => {
+            if was_healthy {
+                tracing::warn!(endpoint = %endpoint.redacted_url(), err = ?e, "endpoint ejected");
+            }
+            endpoint
+                .health()
+                .record_failure(num_slots_behind(&e), backoff, max_backoff);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::future::{select, BoxFuture, Either};
+use serde_json::Value;
+use solana_client::rpc_request::RpcRequest;
+use tower::{BoxError, Service};
+
+use super::{
+    endpoint::Endpoint,
+    health::poll_any_healthy,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+    stats_updater::StatsUpdater,
+};
+
+/// Methods that are hedged by default.
+pub const DEFAULT_HEDGED_METHODS: [RpcRequest; 4] = [
+    RpcRequest::GetLatestBlockhash,
+    RpcRequest::GetAccountInfo,
+    RpcRequest::GetMultipleAccounts,
+    RpcRequest::GetBalance,
+];
+
+/// Methods with side effects, which are not hedged unless explicitly allowed.
+pub fn is_idempotent(method: &RpcRequest) -> bool {
+    !matches!(
+        method,
+        RpcRequest::SendTransaction | RpcRequest::RequestAirdrop
+    )
+}
+
+/// The most recent latencies of successful responses, per method.
+#[derive(Default)]
+struct Latencies {
+    methods: HashMap<RpcRequest, MethodLatencies>,
+}
+
+#[derive(Default)]
+struct MethodLatencies {
+    samples: VecDeque<Duration>,
+    /// The percentile of the samples when it was last computed.
+    cached: Option<Duration>,
+    /// How many samples were recorded since.
+    since_cached: usize,
+}
+
+impl Latencies {
+    const MAX_SAMPLES: usize = 1000;
+
+    fn record(&mut self, method: RpcRequest, latency: Duration) {
+        let latencies = self.methods.entry(method).or_default();
+        if latencies.samples.len() == Self::MAX_SAMPLES {
+            latencies.samples.pop_front();
+        }
+        latencies.samples.push_back(latency);
+        latencies.since_cached += 1;
+    }
+
+    /// Only recomputed once a tenth of the samples are new, since this runs for every request.
+    fn percentile(
+        &mut self,
+        method: &RpcRequest,
+        percentile: f64,
+        min_samples: usize,
+    ) -> Option<Duration> {
+        let latencies = self.methods.get_mut(method)?;
+        let samples = &latencies.samples;
+        if samples.is_empty() || samples.len() < min_samples {
+            return None;
+        }
+        if latencies.cached.is_none() || latencies.since_cached > samples.len() / 10 {
+            let mut samples: Vec<_> = samples.iter().copied().collect();
+            let index = ((samples.len() - 1) as f64 * percentile).round() as usize;
+            latencies.cached = Some(*samples.select_nth_unstable(index).1);
+            latencies.since_cached = 0;
+        }
+        latencies.cached
+    }
+}
+
+/// Sends requests to the primary endpoint, and if it hasn't answered within the given
+/// percentile of recent latencies for that method, sends the same request to the secondary endpoint.
+/// Whichever successful response comes first is returned.
+///
+/// Only [DEFAULT_HEDGED_METHODS] are hedged unless configured otherwise,
+/// and methods with side effects such as `SendTransaction` are never hedged
+/// unless [HedgeService::hedge_non_idempotent] is set.
+/// No request is hedged until `min_samples` latencies have been recorded for its method.
+///
+/// Hedged requests are counted in the `hedged_request_count` of the client's transport stats.
+///
+/// If a [HealthChecker](super::HealthChecker) finds the primary unhealthy but not the secondary,
+/// they swap roles until the primary recovers.
+pub struct HedgeService<S> {
+    primary: Endpoint<S>,
+    secondary: Endpoint<S>,
+    methods: Arc<HashSet<RpcRequest>>,
+    hedge_non_idempotent: bool,
+    percentile: f64,
+    min_samples: usize,
+    latencies: Arc<Mutex<Latencies>>,
+}
+
+impl<S> Clone for HedgeService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            primary: self.primary.clone(),
+            secondary: self.secondary.clone(),
+            methods: self.methods.clone(),
+            hedge_non_idempotent: self.hedge_non_idempotent,
+            percentile: self.percentile,
+            min_samples: self.min_samples,
+            latencies: self.latencies.clone(),
+        }
+    }
+}
+
+impl<S> HedgeService<S> {
+    /// Hedges at the 90th percentile latency, after 10 samples.
+    pub fn new(primary: Endpoint<S>, secondary: Endpoint<S>) -> Self {
+        Self {
+            primary,
+            secondary,
+            methods: Arc::new(DEFAULT_HEDGED_METHODS.into_iter().collect()),
+            hedge_non_idempotent: false,
+            percentile: 0.9,
+            min_samples: 10,
+            latencies: Default::default(),
+        }
+    }
+
+    /// Replace the set of methods that are hedged.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Arc::new(methods.into_iter().collect());
+        self
+    }
+
+    /// Allow hedging methods with side effects, if they are in the set of hedged methods.
+    pub fn hedge_non_idempotent(mut self, hedge_non_idempotent: bool) -> Self {
+        self.hedge_non_idempotent = hedge_non_idempotent;
+        self
+    }
+
+    /// Between 0 and 1, e.g. 0.9 to hedge requests slower than 90% of recent ones.
+    pub fn percentile(mut self, percentile: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&percentile),
+            "percentile must be between 0 and 1"
+        );
+        self.percentile = percentile;
+        self
+    }
+
+    pub fn min_samples(mut self, min_samples: usize) -> Self {
+        self.min_samples = min_samples;
+        self
+    }
+
+    fn should_hedge(&self, method: &RpcRequest) -> bool {
+        self.methods.contains(method) && (self.hedge_non_idempotent || is_idempotent(method))
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for HedgeService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    /// Ready while any endpoint is healthy. The readiness of the endpoint itself
+    /// is checked as each one is called.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        poll_any_healthy([&self.primary, &self.secondary], cx)
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let method = request.0;
+        let delay = if self.should_hedge(&method) {
+            self.latencies
+                .lock()
+                .unwrap()
+                .percentile(&method, self.percentile, self.min_samples)
+        } else {
+            None
+        };
+        // Swap roles if a health checker found the primary unhealthy.
+        let (primary, secondary) = if !self.primary.is_healthy() && self.secondary.is_healthy() {
+            (&self.secondary, self.primary.clone())
+        } else {
+            (&self.primary, self.secondary.clone())
+        };
+        let latencies = self.latencies.clone();
+        let start = Instant::now();
+        let primary_fut = primary.call(request.clone());
+        Box::pin(async move {
+            let record = |result: &SolanaClientResponse, since: Instant| {
+                if result.is_ok() {
+                    latencies.lock().unwrap().record(method, since.elapsed());
+                }
+            };
+            let Some(delay) = delay else {
+                let result = primary_fut.await;
+                record(&result, start);
+                return result;
+            };
+            let primary_fut = match select(primary_fut, Box::pin(tokio::time::sleep(delay))).await {
+                Either::Left((result, _)) => {
+                    record(&result, start);
+                    return result;
+                }
+                Either::Right((_, primary_fut)) => primary_fut,
+            };
+
+            tracing::debug!(%method, ?delay, endpoint = %secondary.redacted_url(), "hedging request");
+            StatsUpdater::with_current(StatsUpdater::add_hedged_request);
+            let hedge_start = Instant::now();
+            let hedge_fut = secondary.call(request);
+            // The first successful response wins. If the first one to finish is an error,
+            // wait for the other one.
+            match select(primary_fut, hedge_fut).await {
+                Either::Left((result, hedge_fut)) => {
+                    if result.is_ok() {
+                        record(&result, start);
+                        return result;
+                    }
+                    let result = hedge_fut.await;
+                    record(&result, hedge_start);
+                    result
+                }
+                Either::Right((result, primary_fut)) => {
+                    if result.is_ok() {
+                        record(&result, hedge_start);
+                        return result;
+                    }
+                    let result = primary_fut.await;
+                    record(&result, start);
+                    result
+                }
+            }
+        })
+    }
+}
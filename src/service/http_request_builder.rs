@@ -1,22 +1,28 @@
 use std::{
-    sync::atomic::{AtomicU64, Ordering},
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
     time::Duration,
 };
 
+use futures::FutureExt;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
-    Method, Url,
+    Method, ResponseBuilderExt, Url,
 };
 use serde_json::{json, Value};
 use tower::{Layer, Service};
 
 pub use super::rpc_sender_impl::RpcClientSender;
-use super::rpc_sender_impl::SolanaClientRequest;
+use super::{redact::Redactor, rpc_sender_impl::SolanaClientRequest};
 
-pub(crate) const JSON_RPC: &'static str = "2.0";
-pub(crate) const APPLICATION_JSON: &'static str = "application/json";
-pub(crate) const SOLANA_CLIENT: &'static str = "solana-client";
+pub(crate) const JSON_RPC: &str = "2.0";
+pub(crate) const APPLICATION_JSON: &str = "application/json";
+pub(crate) const SOLANA_CLIENT: &str = "solana-client";
 
 pub(crate) fn rust_version() -> String {
     format!("rust/{}", solana_version::Version::default())
@@ -37,6 +43,7 @@ pub struct HttpRequestLayer {
     headers: HeaderMap,
     timeout: Duration,
     url: Url,
+    redactor: Redactor,
 }
 
 impl HttpRequestLayer {
@@ -45,14 +52,26 @@ impl HttpRequestLayer {
             headers: Default::default(),
             timeout: Duration::from_secs(30),
             url,
+            redactor: Default::default(),
         }
     }
 
+    /// Configure which secrets are masked, see [Redactor].
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
     pub fn with_header(mut self, k: HeaderName, v: HeaderValue) -> Self {
         self.headers.append(k, v);
         self
     }
 
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
     pub fn with_timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
         self
@@ -69,17 +88,25 @@ impl<S> Layer<S> for HttpRequestLayer {
             Some(self.timeout),
             Some(self.headers.clone()),
         )
+        .with_redactor(&self.redactor)
     }
 }
 
 /// Service for layering in configuration to a [reqwest::Request]
 /// and constructing the JSON-RPC body.
+/// Clones share the same request ID counter.
+///
+/// The URLs of responses and errors are redacted, and so are the headers in request logs,
+/// see [Redactor], so that logging them doesn't leak secrets.
+#[derive(Clone)]
 pub struct HttpJsonRpcRequestService<S> {
     service: S,
-    request_id: AtomicU64,
+    request_id: Arc<AtomicU64>,
     headers: HeaderMap,
     timeout: Duration,
     url: Url,
+    redacted_url: Url,
+    redactor: Redactor,
 }
 
 impl<S> HttpJsonRpcRequestService<S> {
@@ -101,21 +128,29 @@ impl<S> HttpJsonRpcRequestService<S> {
         }
         Self {
             service,
-            request_id: AtomicU64::new(0),
+            request_id: Arc::new(AtomicU64::new(0)),
             headers,
             timeout: timeout.unwrap_or(Duration::from_secs(30)),
+            redacted_url: Redactor::default().redact_url(&url),
             url,
+            redactor: Redactor::default(),
         }
     }
+
+    pub fn with_redactor(mut self, redactor: &Redactor) -> Self {
+        self.redacted_url = redactor.redact_url(&self.url);
+        self.redactor = redactor.clone();
+        self
+    }
 }
 
 impl<S> Service<SolanaClientRequest> for HttpJsonRpcRequestService<S>
 where
-    S: Service<reqwest::Request>,
+    S: Service<reqwest::Request, Response = reqwest::Response, Error = reqwest::Error>,
 {
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = S::Future;
+    type Response = reqwest::Response;
+    type Error = reqwest::Error;
+    type Future = RedactedUrlFuture<S::Future>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.service.poll_ready(cx)
@@ -124,16 +159,68 @@ where
     fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
         let (method, params) = request;
         let request_id = self.request_id.fetch_add(1, Ordering::Relaxed);
+        tracing::Span::current()
+            .record("id", request_id)
+            .record("endpoint", tracing::field::display(&self.redacted_url));
         let body = jsonrpc_request_body(method.to_string(), params, request_id);
 
         let mut headers = HeaderMap::new();
         headers.extend(self.headers.clone());
-        let timeout = self.timeout.clone();
+        let timeout = self.timeout;
+        tracing::trace!(%method, headers = ?self.redactor.redact_headers(&headers), "rpc request headers");
 
         let mut request = reqwest::Request::new(Method::POST, self.url.clone());
         *request.headers_mut() = headers;
         *request.timeout_mut() = Some(timeout);
         *request.body_mut() = Some(body.into());
-        self.service.call(request)
+        RedactedUrlFuture {
+            inner: Box::pin(self.service.call(request)),
+            redacted_url: self.redacted_url.clone(),
+        }
+    }
+}
+
+/// Replaces the URL of responses and errors with the redacted one, so that
+/// errors made from them later on, e.g. for non-success statuses, don't leak it.
+pub struct RedactedUrlFuture<F> {
+    inner: Pin<Box<F>>,
+    redacted_url: Url,
+}
+
+impl<F> Future for RedactedUrlFuture<F>
+where
+    F: Future<Output = Result<reqwest::Response, reqwest::Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = match self.inner.poll_unpin(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(result) => result,
+        };
+        Poll::Ready(
+            result
+                .map(|response| with_url(response, self.redacted_url.clone()))
+                .map_err(|mut e| {
+                    if let Some(url) = e.url_mut() {
+                        *url = self.redacted_url.clone();
+                    }
+                    e
+                }),
+        )
+    }
+}
+
+fn with_url(response: reqwest::Response, url: Url) -> reqwest::Response {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(url);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
     }
+    let response = builder
+        .body(reqwest::Body::from(response))
+        .expect("status and headers of a valid response");
+    reqwest::Response::from(response)
 }
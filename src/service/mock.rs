@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+
+use futures::future::{ready, Ready};
+use serde::Serialize;
+use serde_json::Value;
+use solana_client::{
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::{Response, RpcResponseContext},
+};
+use tower::{BoxError, Service};
+
+use super::rpc_sender_impl::SolanaClientRequest;
+
+/// The JSON-RPC error code for methods the server doesn't know.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Methods whose results are wrapped in a [Response] with the slot they were read at.
+pub const CONTEXT_METHODS: [RpcRequest; 15] = [
+    RpcRequest::GetAccountInfo,
+    RpcRequest::GetBalance,
+    RpcRequest::GetFeeForMessage,
+    RpcRequest::GetLargestAccounts,
+    RpcRequest::GetLatestBlockhash,
+    RpcRequest::GetMultipleAccounts,
+    RpcRequest::GetSignatureStatuses,
+    RpcRequest::GetSupply,
+    RpcRequest::GetTokenAccountBalance,
+    RpcRequest::GetTokenAccountsByDelegate,
+    RpcRequest::GetTokenAccountsByOwner,
+    RpcRequest::GetTokenLargestAccounts,
+    RpcRequest::GetTokenSupply,
+    RpcRequest::IsBlockhashValid,
+    RpcRequest::SimulateTransaction,
+];
+
+type Handler = Arc<dyn Fn(&Value) -> Result<Value, BoxError> + Send + Sync>;
+
+#[derive(Clone)]
+struct Method {
+    handler: Handler,
+    with_context: bool,
+}
+
+#[derive(Default)]
+struct MockState {
+    calls: HashMap<RpcRequest, usize>,
+    expected: HashMap<RpcRequest, usize>,
+}
+
+/// A service for tests that answers requests with typed handlers registered per method,
+/// instead of hand-written `match method` blocks with [ServiceBuilderExt::with_fn](super::builder::ServiceBuilderExt::with_fn).
+///
+/// Results of [CONTEXT_METHODS] are wrapped in a [Response] at the configured slot,
+/// so e.g. a `GetBalance` handler only returns the `u64`. Methods without a handler
+/// fail with a "Method not found" JSON-RPC error, like a real node would.
+///
+/// Clones share the call counts, so keep one to check them after handing the other to a client.
+#[derive(Clone)]
+pub struct MockService {
+    methods: HashMap<RpcRequest, Method>,
+    slot: Arc<AtomicU64>,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl fmt::Debug for MockService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockService")
+            .field("methods", &self.methods.keys().collect::<Vec<_>>())
+            .field("slot", &self.slot.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Default for MockService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockService {
+    /// Responds at slot 0.
+    pub fn new() -> Self {
+        Self {
+            methods: HashMap::new(),
+            slot: Default::default(),
+            state: Default::default(),
+        }
+    }
+
+    /// The slot that wrapped responses are at.
+    pub fn slot(self, slot: u64) -> Self {
+        self.set_slot(slot);
+        self
+    }
+
+    /// Change the slot of wrapped responses, e.g. to simulate the chain moving on.
+    pub fn set_slot(&self, slot: u64) {
+        self.slot.store(slot, Ordering::Relaxed);
+    }
+
+    /// Answer `method` with the result of `handler`, called with the request params.
+    /// The result is wrapped in a [Response] for [CONTEXT_METHODS].
+    pub fn respond<T, F>(self, method: RpcRequest, handler: F) -> Self
+    where
+        T: Serialize,
+        F: Fn(&Value) -> T + Send + Sync + 'static,
+    {
+        self.try_respond(method, move |params| Ok(handler(params)))
+    }
+
+    /// Like [MockService::respond], for handlers that can fail, e.g. with an [RpcError].
+    pub fn try_respond<T, F>(mut self, method: RpcRequest, handler: F) -> Self
+    where
+        T: Serialize,
+        F: Fn(&Value) -> Result<T, BoxError> + Send + Sync + 'static,
+    {
+        let handler = move |params: &Value| Ok(serde_json::to_value(handler(params)?)?);
+        self.methods.insert(
+            method,
+            Method {
+                handler: Arc::new(handler),
+                with_context: CONTEXT_METHODS.contains(&method),
+            },
+        );
+        self
+    }
+
+    /// Answer `method` with the JSON value `handler` returns, as is.
+    pub fn respond_raw<F>(mut self, method: RpcRequest, handler: F) -> Self
+    where
+        F: Fn(&Value) -> Value + Send + Sync + 'static,
+    {
+        self.methods.insert(
+            method,
+            Method {
+                handler: Arc::new(move |params| Ok(handler(params))),
+                with_context: false,
+            },
+        );
+        self
+    }
+
+    /// Fail every `method` request with this JSON-RPC error.
+    pub fn error(self, method: RpcRequest, code: i64, message: &str) -> Self {
+        let message = message.to_string();
+        self.try_respond(method, move |_| -> Result<Value, BoxError> {
+            Err(RpcError::RpcResponseError {
+                code,
+                message: message.clone(),
+                data: RpcResponseErrorData::Empty,
+            }
+            .into())
+        })
+    }
+
+    /// Expect `method` to be called exactly `times` times, see [MockService::assert_expectations].
+    pub fn expect(self, method: RpcRequest, times: usize) -> Self {
+        self.state.lock().unwrap().expected.insert(method, times);
+        self
+    }
+
+    /// How many times `method` was called, including methods without a handler.
+    pub fn calls(&self, method: RpcRequest) -> usize {
+        let state = self.state.lock().unwrap();
+        state.calls.get(&method).copied().unwrap_or_default()
+    }
+
+    /// Panic, listing them, if any expectations set with [MockService::expect] weren't met.
+    pub fn assert_expectations(&self) {
+        let mut unmet: Vec<String> = {
+            let state = self.state.lock().unwrap();
+            state
+                .expected
+                .iter()
+                .filter_map(|(method, expected)| {
+                    let calls = state.calls.get(method).copied().unwrap_or_default();
+                    (calls != *expected)
+                        .then(|| format!("{method}: expected {expected} calls, got {calls}"))
+                })
+                .collect()
+        };
+        unmet.sort();
+        assert!(unmet.is_empty(), "unmet expectations: {}", unmet.join(", "));
+    }
+}
+
+impl Service<SolanaClientRequest> for MockService {
+    type Response = Value;
+    type Error = BoxError;
+    type Future = Ready<Result<Value, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, (method, params): SolanaClientRequest) -> Self::Future {
+        *self.state.lock().unwrap().calls.entry(method).or_default() += 1;
+        ready(match self.methods.get(&method) {
+            Some(Method {
+                handler,
+                with_context: true,
+            }) => handler(&params).map(|value| {
+                serde_json::json!(Response {
+                    context: RpcResponseContext {
+                        slot: self.slot.load(Ordering::Relaxed),
+                        api_version: None,
+                    },
+                    value,
+                })
+            }),
+            Some(Method { handler, .. }) => handler(&params),
+            None => Err(RpcError::RpcResponseError {
+                code: METHOD_NOT_FOUND,
+                message: "Method not found".to_string(),
+                data: RpcResponseErrorData::Empty,
+            }
+            .into()),
+        })
+    }
+}
@@ -67,13 +67,12 @@ fn parse_rpc_error(json: Value) -> SolanaClientResponse {
 /// Parse a generic JSON-RPC response by either:
 /// - Extracting the "result" field from a successful response, or
 /// - Parsing the "error" field from an error response
-#[tracing::instrument]
+///
+/// Bodies aren't logged here, see [TraceLayer](crate::middleware::TraceLayer) for that.
 pub fn parse_response_errors(mut json: Value) -> SolanaClientResponse {
     if json["error"].is_object() {
-        tracing::error!(jsonrpc_error = ?json);
         return parse_rpc_error(json["error"].take());
     }
-    tracing::info!(jsonrpc_response=?json);
     Ok(json["result"].take())
 }
 
@@ -87,7 +86,7 @@ impl<S> Layer<S> for ParseResponseBodyLayer {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParseResponseBody<T> {
     inner: T,
 }
@@ -114,11 +113,12 @@ where
     }
 }
 
+type ResponseBodyFuture = Pin<Box<dyn Future<Output = Result<Value, BoxError>> + Send>>;
+
 pub struct ParseResponseFuture<F> {
     // The response body is awaited and parsed as JSON-RPC output after this
     inner_fut: Pin<Box<F>>,
-    http_response_body_fut:
-        Option<Pin<Box<dyn Future<Output = Result<Value, reqwest::Error>> + Send>>>,
+    http_response_body_fut: Option<ResponseBodyFuture>,
 }
 
 impl<F> ParseResponseFuture<F> {
@@ -146,9 +146,9 @@ where
                     return Poll::Ready(match r {
                         Err(e) => {
                             tracing::error!(http_error=?e);
-                            Err(Box::new(e) as BoxError)
+                            Err(e)
                         }
-                        Result::<Value, _>::Ok(value) => parse_response_errors(value),
+                        Ok(value) => parse_response_errors(value),
                     });
                 }
             }
@@ -157,8 +157,18 @@ where
             Poll::Pending => Poll::Pending,
             Poll::Ready(r) => match r {
                 Ok(r) => {
-                    tracing::info!("{:?}", r);
-                    self.http_response_body_fut = Some(Box::pin(r.json()));
+                    tracing::Span::current().record("status", r.status().as_u16());
+                    // Same as the vanilla `HttpSender`, a non-success status is an error
+                    // regardless of the body, so the status code is available to callers.
+                    if let Err(e) = r.error_for_status_ref() {
+                        tracing::error!(http_error=?e);
+                        return Poll::Ready(Err(Box::new(e) as BoxError));
+                    }
+                    self.http_response_body_fut = Some(Box::pin(async move {
+                        let body = r.bytes().await?;
+                        tracing::Span::current().record("body_size", body.len());
+                        Ok(serde_json::from_slice(&body)?)
+                    }));
                     self.poll(cx)
                 }
                 Err(e) => {
@@ -0,0 +1,288 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt},
+    FutureExt,
+};
+use reqwest::Url;
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use tower::{BoxError, Service};
+
+use super::{
+    endpoint::Endpoint,
+    health::poll_any_healthy,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+};
+
+/// Methods that need a quorum by default.
+pub const DEFAULT_QUORUM_METHODS: [RpcRequest; 3] = [
+    RpcRequest::GetBalance,
+    RpcRequest::GetAccountInfo,
+    RpcRequest::GetTokenAccountBalance,
+];
+
+/// Which `context.slot`s of `Response`-shaped results count towards a quorum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlotAgreement {
+    /// Only the `value` has to agree.
+    #[default]
+    Any,
+    /// The `context.slot` has to agree too.
+    Same,
+    /// Only results at this `context.slot` or newer count, e.g. the slot of a previous read.
+    AtLeast(u64),
+}
+
+/// Not enough endpoints agreed on a response.
+///
+/// An [RpcClient](solana_client::nonblocking::rpc_client::RpcClient) returns it as a
+/// `ClientErrorKind::Custom` error, see [quorum_error](super::errors::quorum_error).
+#[derive(Debug)]
+pub struct QuorumError {
+    pub method: RpcRequest,
+    /// How many endpoints needed to agree.
+    pub quorum: usize,
+    /// How many endpoints agreed on the most common response.
+    pub agreed: usize,
+    /// What each endpoint answered, by redacted URL, in the order they answered.
+    /// Endpoints that had not answered yet when the quorum became impossible are left out.
+    pub responses: Vec<(Url, SolanaClientResponse)>,
+}
+
+impl fmt::Display for QuorumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No quorum for {}: {} of {} required endpoints agreed",
+            self.method, self.agreed, self.quorum
+        )?;
+        for (url, response) in &self.responses {
+            match response {
+                Ok(value) => write!(f, "; {url} answered {value}")?,
+                Err(e) => write!(f, "; {url} failed with {e}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QuorumError {}
+
+impl QuorumError {
+    /// Parse the error of a request of `method` back from its message, e.g. that of a
+    /// `ClientErrorKind::Custom` error. The errors of endpoints only keep their message.
+    pub fn from_message(method: RpcRequest, message: &str) -> Option<Self> {
+        let message = message.strip_prefix(&format!("No quorum for {method}: "))?;
+        let (counts, responses) = message.split_once(" required endpoints agreed")?;
+        let (agreed, quorum) = counts.split_once(" of ")?;
+        // Split into responses, keeping together messages that contain the separator.
+        let mut parts: Vec<(Url, bool, String)> = vec![];
+        for part in responses.split("; ").skip(1) {
+            match response_part(part) {
+                Some((url, ok, text)) => parts.push((url, ok, text.to_string())),
+                None => {
+                    let (_, _, text) = parts.last_mut()?;
+                    text.push_str("; ");
+                    text.push_str(part);
+                }
+            }
+        }
+        let responses = parts
+            .into_iter()
+            .map(|(url, ok, text)| {
+                let response = match ok {
+                    true => Ok(serde_json::from_str(&text).ok()?),
+                    false => Err(text.into()),
+                };
+                Some((url, response))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            method,
+            quorum: quorum.parse().ok()?,
+            agreed: agreed.parse().ok()?,
+            responses,
+        })
+    }
+}
+
+/// The URL of a response in a [QuorumError] message, whether it succeeded, and its value or error.
+fn response_part(part: &str) -> Option<(Url, bool, &str)> {
+    let (url, rest) = part.split_once(' ')?;
+    let url = Url::parse(url).ok()?;
+    match rest.strip_prefix("answered ") {
+        Some(value) => Some((url, true, value)),
+        None => Some((url, false, rest.strip_prefix("failed with ")?)),
+    }
+}
+
+/// Sends each request to every endpoint concurrently, and only returns once `quorum` of them
+/// agree on the response. For `Response`-shaped results, only the `value` has to agree,
+/// and the agreeing response with the newest `context.slot` is returned.
+/// Otherwise a [QuorumError] is returned, which helps detect misbehaving or forked nodes.
+///
+/// Only [DEFAULT_QUORUM_METHODS] need a quorum unless configured otherwise.
+/// Other methods are sent to the first endpoint only.
+pub struct QuorumService<S> {
+    endpoints: Arc<Vec<Endpoint<S>>>,
+    quorum: usize,
+    methods: Arc<HashSet<RpcRequest>>,
+    slot_agreement: SlotAgreement,
+}
+
+impl<S> Clone for QuorumService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            endpoints: self.endpoints.clone(),
+            quorum: self.quorum,
+            methods: self.methods.clone(),
+            slot_agreement: self.slot_agreement,
+        }
+    }
+}
+
+impl<S> QuorumService<S> {
+    /// Panics unless `quorum` is between 1 and the number of endpoints.
+    pub fn new(endpoints: Vec<Endpoint<S>>, quorum: usize) -> Self {
+        assert!(
+            (1..=endpoints.len()).contains(&quorum),
+            "QuorumService requires a quorum between 1 and the number of endpoints"
+        );
+        Self {
+            endpoints: Arc::new(endpoints),
+            quorum,
+            methods: Arc::new(DEFAULT_QUORUM_METHODS.into_iter().collect()),
+            slot_agreement: SlotAgreement::Any,
+        }
+    }
+
+    /// Replace the set of methods that need a quorum.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = RpcRequest>) -> Self {
+        self.methods = Arc::new(methods.into_iter().collect());
+        self
+    }
+
+    /// Only count `Response`-shaped results as agreeing if they are also at the same `context.slot`.
+    pub fn require_same_slot(self, require_same_slot: bool) -> Self {
+        self.slot_agreement(match require_same_slot {
+            true => SlotAgreement::Same,
+            false => SlotAgreement::Any,
+        })
+    }
+
+    pub fn slot_agreement(mut self, slot_agreement: SlotAgreement) -> Self {
+        self.slot_agreement = slot_agreement;
+        self
+    }
+}
+
+/// The part of a response that has to be equal for endpoints to agree,
+/// or `None` if it doesn't count towards a quorum.
+fn agreement_key(value: &Value, slot_agreement: SlotAgreement) -> Option<Value> {
+    let (Some(context), Some(inner)) = (value.get("context"), value.get("value")) else {
+        return Some(value.clone());
+    };
+    match slot_agreement {
+        SlotAgreement::Any => Some(inner.clone()),
+        SlotAgreement::Same => Some(json!([inner, context["slot"]])),
+        SlotAgreement::AtLeast(min_slot) => {
+            (context_slot(value) >= min_slot).then(|| inner.clone())
+        }
+    }
+}
+
+fn context_slot(value: &Value) -> u64 {
+    value["context"]["slot"].as_u64().unwrap_or_default()
+}
+
+impl<S> Service<SolanaClientRequest> for QuorumService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    /// Ready while any endpoint is healthy. The readiness of the endpoint itself
+    /// is checked as each one is called.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        poll_any_healthy(self.endpoints.iter(), cx)
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let method = request.0;
+        if !self.methods.contains(&method) {
+            return self.endpoints[0].call(request);
+        }
+        let quorum = self.quorum;
+        let slot_agreement = self.slot_agreement;
+        let mut pending: FuturesUnordered<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let url = endpoint.redacted_url().clone();
+                endpoint.call(request.clone()).map(move |r| (url, r))
+            })
+            .collect();
+        Box::pin(async move {
+            // Responses grouped by what they agree on.
+            let mut groups: Vec<(Value, Vec<Value>)> = vec![];
+            let mut responses = vec![];
+            while let Some((url, response)) = pending.next().await {
+                let key = response
+                    .as_ref()
+                    .ok()
+                    .and_then(|value| Some((value, agreement_key(value, slot_agreement)?)));
+                if let Some((value, key)) = key {
+                    let group = match groups.iter().position(|(k, _)| *k == key) {
+                        Some(i) => &mut groups[i].1,
+                        None => {
+                            groups.push((key, vec![]));
+                            &mut groups.last_mut().unwrap().1
+                        }
+                    };
+                    group.push(value.clone());
+                    if group.len() >= quorum {
+                        return Ok(group
+                            .iter()
+                            .max_by_key(|v| context_slot(v))
+                            .unwrap()
+                            .clone());
+                    }
+                } else if response.is_err() {
+                    tracing::warn!(%method, endpoint = %url, "quorum request failed");
+                }
+                responses.push((url, response));
+                let agreed = groups
+                    .iter()
+                    .map(|(_, g)| g.len())
+                    .max()
+                    .unwrap_or_default();
+                if agreed + pending.len() < quorum {
+                    break;
+                }
+            }
+            let agreed = groups
+                .iter()
+                .map(|(_, g)| g.len())
+                .max()
+                .unwrap_or_default();
+            let error = QuorumError {
+                method,
+                quorum,
+                agreed,
+                responses,
+            };
+            tracing::error!(%error);
+            Err(Box::new(error) as BoxError)
+        })
+    }
+}
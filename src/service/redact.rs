@@ -0,0 +1,198 @@
+use std::collections::{BTreeSet, HashSet};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION},
+    Url,
+};
+
+/// What secrets are replaced with.
+pub const REDACTED: &str = "REDACTED";
+
+/// Secrets shorter than this are only masked in URLs and headers, and not in
+/// [Redactor::redact_text], where they could match unrelated words.
+pub const MIN_SECRET_LEN: usize = 8;
+
+/// What [Url] percent-encodes in path segments.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// What [Url] percent-encodes in query values, except for spaces, which become `+`.
+const QUERY_VALUE: &AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
+/// Query parameters that are redacted by default.
+pub const DEFAULT_REDACTED_QUERY_PARAMS: [&str; 6] = [
+    "api-key",
+    "api_key",
+    "apikey",
+    "key",
+    "token",
+    "access_token",
+];
+
+/// Masks secrets, e.g. provider API keys, in URLs, headers, and error messages,
+/// so they don't end up in logs, metrics or [RpcSender::url](solana_client::rpc_sender::RpcSender::url).
+/// The real URL is still used for requests.
+///
+/// By default, [DEFAULT_REDACTED_QUERY_PARAMS], URL passwords, and the `Authorization`
+/// and `x-api-key` headers are redacted. Keys in the path, e.g. `https://rpc.example.com/v2/<key>`,
+/// need to be configured with [Redactor::path_segment].
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    query_params: HashSet<String>,
+    path_segments: BTreeSet<usize>,
+    headers: HashSet<HeaderName>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            query_params: DEFAULT_REDACTED_QUERY_PARAMS
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            path_segments: BTreeSet::new(),
+            headers: [AUTHORIZATION, HeaderName::from_static("x-api-key")]
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl Redactor {
+    /// Also redact this query parameter, matched case-insensitively.
+    pub fn query_param(mut self, name: &str) -> Self {
+        self.query_params.insert(name.to_lowercase());
+        self
+    }
+
+    /// Also redact the path segment at `index`, starting from zero.
+    pub fn path_segment(mut self, index: usize) -> Self {
+        self.path_segments.insert(index);
+        self
+    }
+
+    /// Also redact this header.
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.headers.insert(name);
+        self
+    }
+
+    fn is_secret_param(&self, name: &str) -> bool {
+        self.query_params.contains(&name.to_lowercase())
+    }
+
+    pub fn redact_url(&self, url: &Url) -> Url {
+        let mut redacted = url.clone();
+        if url.password().is_some() {
+            let _ = redacted.set_password(Some(REDACTED));
+        }
+        if url
+            .query_pairs()
+            .any(|(name, _)| self.is_secret_param(&name))
+        {
+            let pairs: Vec<_> = url
+                .query_pairs()
+                .map(|(name, value)| match self.is_secret_param(&name) {
+                    true => (name, REDACTED.into()),
+                    false => (name, value),
+                })
+                .collect();
+            redacted.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        if !self.path_segments.is_empty() {
+            if let Some(segments) = url.path_segments() {
+                let segments: Vec<_> = segments
+                    .enumerate()
+                    .map(|(i, segment)| match self.path_segments.contains(&i) {
+                        true if !segment.is_empty() => REDACTED,
+                        _ => segment,
+                    })
+                    .collect();
+                redacted.set_path(&segments.join("/"));
+            }
+        }
+        redacted
+    }
+
+    pub fn redact_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut redacted = headers.clone();
+        for name in &self.headers {
+            if redacted.contains_key(name) {
+                redacted.insert(name, HeaderValue::from_static(REDACTED));
+            }
+        }
+        redacted
+    }
+
+    /// The secrets in `url` and `headers`, percent-decoded.
+    pub fn secrets(&self, url: &Url, headers: &HeaderMap) -> Vec<String> {
+        let decode = |encoded: &str| percent_decode_str(encoded).decode_utf8_lossy().into_owned();
+        let mut secrets: Vec<String> = url.password().map(decode).into_iter().collect();
+        secrets.extend(
+            url.query_pairs()
+                .filter(|(name, _)| self.is_secret_param(name))
+                .map(|(_, value)| value.into_owned()),
+        );
+        if let Some(segments) = url.path_segments() {
+            secrets.extend(
+                segments
+                    .enumerate()
+                    .filter(|(i, _)| self.path_segments.contains(i))
+                    .map(|(_, segment)| decode(segment)),
+            );
+        }
+        secrets.extend(
+            self.headers
+                .iter()
+                .flat_map(|name| headers.get_all(name))
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| {
+                    // Also the credentials alone, e.g. the token of "Bearer <token>"
+                    let credentials = value.split_once(' ').map(|(_, credentials)| credentials);
+                    [Some(value), credentials].into_iter().flatten()
+                })
+                .map(String::from),
+        );
+        secrets.retain(|secret| !secret.is_empty());
+        secrets
+    }
+
+    /// Replace the secrets of `url` and `headers` anywhere in `text`, e.g. an error message,
+    /// as they are and as they are encoded in URL paths and queries.
+    /// Secrets shorter than [MIN_SECRET_LEN] are left alone.
+    pub fn redact_text(&self, text: &str, url: &Url, headers: &HeaderMap) -> String {
+        let mut forms: Vec<String> = self
+            .secrets(url, headers)
+            .into_iter()
+            .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+            .flat_map(|secret| {
+                let path = utf8_percent_encode(&secret, PATH_SEGMENT).to_string();
+                let query = utf8_percent_encode(&secret, QUERY_VALUE)
+                    .to_string()
+                    .replace("%20", "+");
+                [secret, path, query]
+            })
+            .collect();
+        // Longer forms first, so that a secret inside another one doesn't leave part of it
+        forms.sort_by_key(|form| std::cmp::Reverse(form.len()));
+        forms.dedup();
+        forms.iter().fold(text.to_string(), |text, form| {
+            text.replace(form.as_str(), REDACTED)
+        })
+    }
+}
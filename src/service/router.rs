@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::future::BoxFuture;
+use serde_json::Value;
+use solana_client::rpc_request::RpcRequest;
+use tower::{util::BoxService, BoxError, Service};
+
+use super::{
+    endpoint::call_shared,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+};
+
+/// A type-erased service, so that routes can lead to services of different types.
+pub type BoxRpcService = BoxService<SolanaClientRequest, Value, BoxError>;
+
+/// Dispatches each request to an inner service chosen by its [RpcRequest] method,
+/// e.g. `SendTransaction` to a staked node and `GetProgramAccounts` to a dedicated one.
+/// Methods without a route go to the default service.
+#[derive(Clone)]
+pub struct RouterService {
+    routes: HashMap<RpcRequest, usize>,
+    services: Vec<Arc<tokio::sync::Mutex<BoxRpcService>>>,
+}
+
+impl RouterService {
+    /// Every method is sent to `default` until routed elsewhere.
+    pub fn new<S>(default: S) -> Self
+    where
+        S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        Self {
+            routes: HashMap::new(),
+            services: vec![Arc::new(tokio::sync::Mutex::new(BoxService::new(default)))],
+        }
+    }
+
+    /// Send the given methods to `service`.
+    /// A method routed more than once goes to the service it was last routed to.
+    pub fn route<S>(mut self, methods: impl IntoIterator<Item = RpcRequest>, service: S) -> Self
+    where
+        S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        let index = self.services.len();
+        self.services
+            .push(Arc::new(tokio::sync::Mutex::new(BoxService::new(service))));
+        self.routes
+            .extend(methods.into_iter().map(|method| (method, index)));
+        self
+    }
+}
+
+impl Service<SolanaClientRequest> for RouterService {
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    /// Readiness is checked on the chosen service only, once the request is known.
+    /// That way a slow or rate-limited route does not hold up the others.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let index = self.routes.get(&request.0).copied().unwrap_or(0);
+        call_shared(&self.services[index], request)
+    }
+}
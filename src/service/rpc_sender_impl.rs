@@ -1,22 +1,24 @@
 use crate::middleware::TooManyRequestsRetry;
 use crate::service::stats_updater::{StatsUpdater, TransportStats};
 use futures::future::BoxFuture;
-use reqwest::Url;
+use reqwest::{header::HeaderMap, Url};
 use serde_json::Value;
 use solana_client::client_error::{ClientError, ClientErrorKind};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_client::RpcClientConfig;
-use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_request::{RpcError, RpcRequest};
 use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
 use solana_sdk::commitment_config::CommitmentConfig;
 use std::future::Future;
 use std::ops::Deref;
 use std::sync::{Arc, RwLock};
-use tower::retry::Retry;
+use std::time::Instant;
+use tower::retry::{Retry, RetryLayer};
 use tower::util::Either;
 use tower::{BoxError, Service, ServiceBuilder, ServiceExt};
 
 use super::parse_response_body::{ParseResponseBody, ParseResponseBodyLayer};
+use super::redact::Redactor;
 use super::{HttpJsonRpcRequestService, HttpRequestLayer};
 
 /// The data types sent to `RpcSender::send`, grouped into a tuple.
@@ -32,17 +34,65 @@ pub struct RpcClientSender<T> {
     service: Arc<tokio::sync::RwLock<T>>,
     stats: Arc<RwLock<TransportStats>>,
     url: String,
+    redactor: Redactor,
+    redacted_url: String,
+    /// The headers sent with requests, to mask their secrets in errors.
+    headers: HeaderMap,
 }
 
 impl RpcClientSender<DefaultHttpService> {
     pub fn new_http(url: Url) -> Self {
         let service = default_http_service(url.clone());
-        let stats = Arc::new(RwLock::new(TransportStats::default()));
-        Self {
-            service: Arc::new(tokio::sync::RwLock::new(service)),
-            url: url.to_string(),
-            stats,
+        Self::new_with_service(url.to_string(), service)
+    }
+}
+
+impl<S> RpcClientSender<S> {
+    /// Configure which secrets in the URL are masked in [RpcSender::url], stats and errors.
+    /// By default, only the usual ones are, see [Redactor].
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redacted_url = redact_url_str(&redactor, &self.url);
+        self.redactor = redactor;
+        self
+    }
+
+    /// The headers that the service sends with requests, e.g. those of an [HttpRequestLayer],
+    /// so that their secrets are masked in errors too.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    fn redact_error(&self, mut err: ClientError) -> ClientError {
+        let Ok(url) = self.url.parse() else {
+            return err;
+        };
+        let redact = |message: &mut String| {
+            *message = self.redactor.redact_text(message, &url, &self.headers);
+        };
+        match &mut err.kind {
+            ClientErrorKind::Custom(message)
+            | ClientErrorKind::RpcError(
+                RpcError::RpcRequestError(message)
+                | RpcError::RpcResponseError { message, .. }
+                | RpcError::ParseError(message)
+                | RpcError::ForUser(message),
+            ) => redact(message),
+            ClientErrorKind::Reqwest(e) => {
+                if let Some(error_url) = e.url_mut() {
+                    *error_url = self.redactor.redact_url(error_url);
+                }
+            }
+            _ => {}
         }
+        err
+    }
+}
+
+fn redact_url_str(redactor: &Redactor, url: &str) -> String {
+    match url.parse() {
+        Ok(url) => redactor.redact_url(&url).to_string(),
+        Err(_) => url.to_string(),
     }
 }
 
@@ -52,11 +102,25 @@ where
     S::Future: Send + 'static,
 {
     pub fn new_with_service(url: String, service: S) -> Self {
-        let stats = Arc::new(RwLock::new(TransportStats::default()));
+        Self::new_with_stats(url, service, Default::default())
+    }
+
+    /// The transport stats, including per method and per endpoint stats,
+    /// which are left out of [RpcSender::get_transport_stats].
+    pub fn transport_stats(&self) -> Arc<RwLock<TransportStats>> {
+        self.stats.clone()
+    }
+
+    /// Share the transport stats with services in the stack that add to them.
+    pub fn new_with_stats(url: String, service: S, stats: Arc<RwLock<TransportStats>>) -> Self {
+        let redactor = Redactor::default();
         Self {
             service: Arc::new(tokio::sync::RwLock::new(service)),
-            url: url.clone(),
+            redacted_url: redact_url_str(&redactor, &url),
+            url,
             stats,
+            redactor,
+            headers: HeaderMap::new(),
         }
     }
 }
@@ -68,9 +132,11 @@ where
 {
     /// Defaults to "finalized" commitment.
     pub fn into_rpc_client(self, commitment: Option<CommitmentConfig>) -> RpcClient {
-        let mut config = RpcClientConfig::default();
-        config.commitment_config = commitment.unwrap_or(CommitmentConfig::finalized());
-        RpcClient::new_sender(self, Default::default())
+        let config = RpcClientConfig {
+            commitment_config: commitment.unwrap_or(CommitmentConfig::finalized()),
+            ..Default::default()
+        };
+        RpcClient::new_sender(self, config)
     }
 }
 
@@ -85,23 +151,34 @@ where
         request: RpcRequest,
         params: serde_json::Value,
     ) -> Result<serde_json::Value, ClientError> {
-        let _stats_updater = StatsUpdater::new(self.stats.clone());
+        let stats_updater = Arc::new(StatsUpdater::for_request(
+            self.stats.clone(),
+            request,
+            Some(self.redacted_url.clone()),
+        ));
+        let queued_since = Instant::now();
         let mut service = self.service.write().await;
         // We are fine with blocking all other write locks while this awaits,
         // because if one is blocked, they are all blocked.
         if let Err(e) = service.ready().await {
             tracing::error!(err=?e);
         }
-        let fut = service.call((request, params));
+        stats_updater.add_queued_time(queued_since.elapsed());
+        let fut = stats_updater
+            .clone()
+            .sync_scope(|| service.call((request, params)));
         drop(service);
-        let resp = fut.await.map_err(|e| match e.downcast::<ClientError>() {
-            Ok(client_error) => *client_error,
-            Err(e) => {
-                tracing::error!(err=?e);
-                ClientError::new_with_request(ClientErrorKind::Custom(format!("{e}")), request)
-            }
+        let resp = stats_updater.clone().scope(fut).await;
+        stats_updater.set_result(&resp);
+        let resp = resp.map_err(|e| {
+            self.redact_error(match e.downcast::<ClientError>() {
+                Ok(client_error) => *client_error,
+                Err(e) => {
+                    tracing::error!(err=?e);
+                    ClientError::new_with_request(ClientErrorKind::Custom(format!("{e}")), request)
+                }
+            })
         })?;
-        tracing::info!(rpc_response=?resp);
         Ok(resp)
     }
 
@@ -109,8 +186,9 @@ where
         self.stats.read().unwrap().deref().into()
     }
 
+    /// Secrets in the URL are redacted.
     fn url(&self) -> String {
-        self.url.clone()
+        self.redacted_url.clone()
     }
 }
 
@@ -123,6 +201,40 @@ pub type HttpServiceOptionalRetry = ParseResponseBody<
     >,
 >;
 
+/// An HTTP client that retries 429 responses up to `retry_429` times, or not at all if zero.
+pub fn http_service(url: Url, retry_429: usize) -> HttpServiceOptionalRetry {
+    http_service_with_redactor(url, retry_429, &Default::default())
+}
+
+/// Like [http_service], masking the configured secrets in errors, see [Redactor].
+pub fn http_service_with_redactor(
+    url: Url,
+    retry_429: usize,
+    redactor: &Redactor,
+) -> HttpServiceOptionalRetry {
+    http_service_with_headers(url, retry_429, redactor, HeaderMap::new())
+}
+
+/// Like [http_service_with_redactor], sending these headers with every request.
+pub fn http_service_with_headers(
+    url: Url,
+    retry_429: usize,
+    redactor: &Redactor,
+    headers: HeaderMap,
+) -> HttpServiceOptionalRetry {
+    let retry_layer =
+        (retry_429 > 0).then(|| RetryLayer::new(TooManyRequestsRetry::new(retry_429)));
+    ServiceBuilder::new()
+        .layer(ParseResponseBodyLayer)
+        .layer(
+            HttpRequestLayer::new(url)
+                .with_redactor(redactor.clone())
+                .with_headers(headers),
+        )
+        .option_layer(retry_layer)
+        .service(reqwest_client())
+}
+
 pub fn default_http_service(url: Url) -> DefaultHttpService {
     ServiceBuilder::new()
         .layer(ParseResponseBodyLayer)
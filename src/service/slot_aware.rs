@@ -0,0 +1,224 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::future::{join_all, BoxFuture};
+use reqwest::Url;
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use tower::{BoxError, Service};
+
+use super::{
+    endpoint::Endpoint,
+    health::poll_any_healthy,
+    rpc_sender_impl::{SolanaClientRequest, SolanaClientResponse},
+};
+
+/// The latest slot observed for an endpoint, and whether it is lagging behind the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointSlot {
+    pub url: Url,
+    /// `None` until a slot has been observed.
+    pub slot: Option<u64>,
+    pub degraded: bool,
+}
+
+struct SlotTracker<S> {
+    endpoints: Vec<Endpoint<S>>,
+    /// Zero until a slot has been observed.
+    slots: Vec<AtomicU64>,
+    degraded: Vec<AtomicBool>,
+    max_slot_lag: u64,
+    next: AtomicUsize,
+}
+
+impl<S> SlotTracker<S> {
+    fn observe(&self, index: usize, slot: u64) {
+        let previous = self.slots[index].fetch_max(slot, Ordering::Relaxed);
+        if slot <= previous {
+            return;
+        }
+        // One snapshot, so that concurrent updates can't put a slot above the highest one
+        let slots: Vec<u64> = self
+            .slots
+            .iter()
+            .map(|s| s.load(Ordering::Relaxed))
+            .collect();
+        let highest = slots.iter().copied().max().unwrap_or_default();
+        for ((i, endpoint), slot) in self.endpoints.iter().enumerate().zip(slots) {
+            let degraded = slot != 0 && highest - slot > self.max_slot_lag;
+            if self.degraded[i].swap(degraded, Ordering::Relaxed) != degraded {
+                if degraded {
+                    tracing::warn!(endpoint = %endpoint.redacted_url(), slot, highest, "endpoint degraded");
+                } else {
+                    tracing::info!(endpoint = %endpoint.redacted_url(), slot, highest, "endpoint caught up");
+                }
+            }
+        }
+    }
+
+    /// Round-robin over the endpoints that are not degraded, preferring healthy ones.
+    fn pick(&self) -> usize {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let len = self.endpoints.len();
+        let up_to_date = || {
+            (0..len)
+                .map(|offset| (start + offset) % len)
+                .filter(|i| !self.degraded[*i].load(Ordering::Relaxed))
+        };
+        up_to_date()
+            .find(|i| self.endpoints[*i].is_healthy())
+            .or_else(|| up_to_date().next())
+            .unwrap_or(start % len)
+    }
+}
+
+/// Spreads requests over the endpoints that are within `max_slot_lag` slots
+/// of the highest slot observed on any endpoint. Endpoints further behind are marked as degraded,
+/// and get no requests until they catch up.
+///
+/// Slots are observed from `getSlot` responses, and from the `context.slot` of any
+/// `Response`-shaped result. Endpoints whose slot is not known yet are not degraded.
+/// Among up-to-date endpoints, those that a [HealthChecker](super::HealthChecker)
+/// found unhealthy are only used if there is no other choice.
+///
+/// Responses at different commitment levels report different slots, so either leave
+/// some room for that in `max_slot_lag`, or poll slots with [SlotAwareService::spawn_slot_poller],
+/// which uses "processed" commitment.
+pub struct SlotAwareService<S> {
+    tracker: Arc<SlotTracker<S>>,
+}
+
+impl<S> Clone for SlotAwareService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            tracker: self.tracker.clone(),
+        }
+    }
+}
+
+impl<S> SlotAwareService<S> {
+    /// Panics if `endpoints` is empty.
+    pub fn new(endpoints: Vec<Endpoint<S>>, max_slot_lag: u64) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "SlotAwareService requires at least one endpoint"
+        );
+        let len = endpoints.len();
+        Self {
+            tracker: Arc::new(SlotTracker {
+                endpoints,
+                slots: (0..len).map(|_| AtomicU64::new(0)).collect(),
+                degraded: (0..len).map(|_| AtomicBool::new(false)).collect(),
+                max_slot_lag,
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// The latest observed slot of each endpoint, in the order they were given.
+    pub fn endpoint_slots(&self) -> Vec<EndpointSlot> {
+        let tracker = &self.tracker;
+        tracker
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, endpoint)| {
+                let slot = tracker.slots[i].load(Ordering::Relaxed);
+                EndpointSlot {
+                    url: endpoint.url().clone(),
+                    slot: (slot != 0).then_some(slot),
+                    degraded: tracker.degraded[i].load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<S> SlotAwareService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    /// Call `getSlot` on every endpoint, at "processed" commitment.
+    pub async fn refresh_slots(&self) {
+        let tracker = self.tracker.clone();
+        let polls = tracker.endpoints.iter().enumerate().map(|(i, endpoint)| {
+            let tracker = tracker.clone();
+            let fut = endpoint.call((RpcRequest::GetSlot, json!([{"commitment": "processed"}])));
+            async move {
+                match fut.await {
+                    Ok(value) => {
+                        if let Some(slot) = value.as_u64() {
+                            tracker.observe(i, slot);
+                        }
+                    }
+                    Err(e) => {
+                        let url = tracker.endpoints[i].redacted_url();
+                        tracing::warn!(endpoint = %url, err = ?e, "failed to poll slot");
+                    }
+                }
+            }
+        });
+        join_all(polls).await;
+    }
+
+    /// Refresh slots every `interval`, until the returned task is aborted
+    /// or every clone of this service is dropped. Must be called from within a tokio runtime.
+    pub fn spawn_slot_poller(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let tracker = Arc::downgrade(&self.tracker);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(tracker) = tracker.upgrade() else {
+                    break;
+                };
+                SlotAwareService { tracker }.refresh_slots().await;
+            }
+        })
+    }
+}
+
+/// The slot a response was observed at, if it says.
+fn response_slot(request: &RpcRequest, value: &Value) -> Option<u64> {
+    match request {
+        RpcRequest::GetSlot => value.as_u64(),
+        _ => value["context"]["slot"].as_u64(),
+    }
+}
+
+impl<S> Service<SolanaClientRequest> for SlotAwareService<S>
+where
+    S: Service<SolanaClientRequest, Response = Value, Error = BoxError> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Value;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, SolanaClientResponse>;
+
+    /// Ready while any endpoint is healthy. The readiness of the endpoint itself
+    /// is checked on the chosen one, when called.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        poll_any_healthy(&self.tracker.endpoints, cx)
+    }
+
+    fn call(&mut self, request: SolanaClientRequest) -> Self::Future {
+        let tracker = self.tracker.clone();
+        let index = tracker.pick();
+        let method = request.0;
+        let fut = tracker.endpoints[index].call(request);
+        Box::pin(async move {
+            let value = fut.await?;
+            if let Some(slot) = response_slot(&method, &value) {
+                tracker.observe(index, slot);
+            }
+            Ok(value)
+        })
+    }
+}
@@ -1,6 +1,15 @@
-use std::time::{Duration, Instant};
-use std::sync::{Arc, RwLock};
+use solana_client::rpc_request::RpcRequest;
 use solana_rpc_client::rpc_sender::RpcTransportStats;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use super::{errors::rpc_error_code, rpc_sender_impl::SolanaClientResponse};
+
+tokio::task_local! {
+    static CURRENT: Arc<StatsUpdater>;
+}
 
 #[derive(Default, Clone, Debug)]
 pub struct TransportStats {
@@ -13,22 +22,195 @@ pub struct TransportStats {
     /// Total amount of waiting time due to RPC server rate limiting
     /// (a subset of `elapsed_time`)
     pub rate_limited_time: Duration,
+
+    /// Total amount of time requests waited for the service to become ready
+    /// (a subset of `elapsed_time`)
+    pub queued_time: Duration,
+
+    /// Number of times a request was retried, by any retry policy in the stack
+    pub retry_count: usize,
+
+    /// Number of requests that were also sent to a second endpoint,
+    /// because the first one was slow to respond
+    pub hedged_request_count: usize,
+
+    /// Stats for each method
+    pub methods: HashMap<RpcRequest, RequestStats>,
+
+    /// Stats for each endpoint, by URL. Services over several endpoints
+    /// count each request sent to each one of them.
+    pub endpoints: HashMap<String, RequestStats>,
+}
+
+/// Request and error counts, and latencies, of a method or an endpoint.
+#[derive(Default, Clone, Debug)]
+pub struct RequestStats {
+    pub request_count: usize,
+    pub error_count: usize,
+    /// Errors that came with a JSON-RPC error code, by code
+    pub errors_by_code: HashMap<i64, usize>,
+    pub latency: LatencyHistogram,
+    /// Requests answered by a [ResponseCacheService](crate::middleware::cache::ResponseCacheService)
+    pub cache_hits: usize,
+    /// Requests a [ResponseCacheService](crate::middleware::cache::ResponseCacheService)
+    /// had to pass on
+    pub cache_misses: usize,
+    /// Requests answered with an expired response, while it was being refreshed
+    /// or because refreshing it failed
+    pub stale_responses: usize,
+}
+
+impl RequestStats {
+    fn record(&mut self, latency: Duration, outcome: Outcome) {
+        self.request_count += 1;
+        self.latency.record(latency);
+        if let Err(code) = outcome {
+            self.error_count += 1;
+            if let Some(code) = code {
+                *self.errors_by_code.entry(code).or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Each bucket is this much wider than the previous one.
+const BUCKET_GROWTH: f64 = 1.1;
+
+/// A latency histogram with logarithmic buckets, so quantiles are accurate to about 10%.
+#[derive(Default, Clone, Debug)]
+pub struct LatencyHistogram {
+    /// Counts of latencies, bucket `i` holding those up to `BUCKET_GROWTH^(i+1)` microseconds.
+    buckets: Vec<u64>,
+    count: u64,
+    sum: Duration,
 }
 
-impl Into<RpcTransportStats> for &TransportStats {
-    fn into(self) -> RpcTransportStats {
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().max(1) as f64;
+        let i = (micros.ln() / BUCKET_GROWTH.ln()) as usize;
+        if self.buckets.len() <= i {
+            self.buckets.resize(i + 1, 0);
+        }
+        self.buckets[i] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// How many latencies fell in buckets that end at or below `latency`.
+    pub fn count_below(&self, latency: Duration) -> u64 {
+        let micros = latency.as_micros() as f64;
+        self.buckets
+            .iter()
+            .enumerate()
+            .take_while(|(i, _)| BUCKET_GROWTH.powi(*i as i32 + 1) <= micros)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        self.sum / self.count as u32
+    }
+
+    /// The latency below which a fraction `q` (between 0 and 1) of the requests fall,
+    /// or zero if nothing was recorded.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(BUCKET_GROWTH.powi(i as i32 + 1) as u64);
+            }
+        }
+        Duration::ZERO
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.quantile(0.5)
+    }
+
+    pub fn p90(&self) -> Duration {
+        self.quantile(0.9)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.quantile(0.99)
+    }
+}
+
+/// Whether a request succeeded, or else its JSON-RPC error code if it had one.
+type Outcome = Result<(), Option<i64>>;
+
+fn outcome(result: &SolanaClientResponse) -> Outcome {
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(rpc_error_code(e)),
+    }
+}
+
+impl From<&TransportStats> for RpcTransportStats {
+    fn from(stats: &TransportStats) -> Self {
         RpcTransportStats {
-            request_count: self.request_count,
-            elapsed_time: self.elapsed_time.clone(),
-            rate_limited_time: self.rate_limited_time.clone(),
+            request_count: stats.request_count,
+            elapsed_time: stats.elapsed_time,
+            rate_limited_time: stats.rate_limited_time,
         }
     }
 }
 
+/// Collects the stats of a single request, and adds them to the [TransportStats] when dropped.
+///
+/// While the request is in flight, [StatsUpdater::with_current] gives access to its updater,
+/// so that services anywhere in the stack, e.g. retry policies, can report back.
+/// This only works within the task that awaits the request.
+/// Updaters created while another one is current, e.g. by a [MetricsLayer](crate::middleware::MetricsLayer)
+/// further down the stack, pass those reports on to the outer one too.
 pub struct StatsUpdater {
     stats: Arc<RwLock<TransportStats>>,
     request_start_time: Instant,
+    request: Option<(RpcRequest, Option<String>)>,
+    parent: Option<Arc<StatsUpdater>>,
+    pending: Mutex<PendingStats>,
+}
+
+struct PendingStats {
     rate_limited_time: Duration,
+    queued_time: Duration,
+    retry_count: usize,
+    hedged_requests: usize,
+    cache_hits: usize,
+    cache_misses: usize,
+    stale_responses: usize,
+    outcome: Outcome,
+    endpoints: Vec<(String, Duration, Outcome)>,
+}
+
+impl Default for PendingStats {
+    fn default() -> Self {
+        Self {
+            rate_limited_time: Duration::ZERO,
+            queued_time: Duration::ZERO,
+            retry_count: 0,
+            hedged_requests: 0,
+            cache_hits: 0,
+            cache_misses: 0,
+            stale_responses: 0,
+            outcome: Ok(()),
+            endpoints: vec![],
+        }
+    }
 }
 
 impl StatsUpdater {
@@ -36,20 +218,121 @@ impl StatsUpdater {
         Self {
             stats,
             request_start_time: Instant::now(),
-            rate_limited_time: Duration::default(),
+            request: None,
+            parent: CURRENT.try_with(Arc::clone).ok(),
+            pending: Default::default(),
         }
     }
 
-    pub fn add_rate_limited_time(&mut self, duration: Duration) {
-        self.rate_limited_time += duration;
+    /// Also count the request in the stats of `method`, and in those of the endpoint at `url`, if any,
+    /// unless responses from other endpoints are reported with [StatsUpdater::add_endpoint_response].
+    pub fn for_request(
+        stats: Arc<RwLock<TransportStats>>,
+        method: RpcRequest,
+        url: Option<String>,
+    ) -> Self {
+        let mut updater = Self::new(stats);
+        updater.request = Some((method, url));
+        updater
+    }
+
+    pub fn set_result(&self, result: &SolanaClientResponse) {
+        self.pending.lock().unwrap().outcome = outcome(result);
+    }
+
+    pub fn add_endpoint_response(
+        &self,
+        url: &str,
+        latency: Duration,
+        result: &SolanaClientResponse,
+    ) {
+        self.update(|pending| {
+            pending
+                .endpoints
+                .push((url.to_string(), latency, outcome(result)))
+        });
+    }
+
+    pub fn add_rate_limited_time(&self, duration: Duration) {
+        self.update(|pending| pending.rate_limited_time += duration);
+    }
+
+    pub fn add_queued_time(&self, duration: Duration) {
+        self.update(|pending| pending.queued_time += duration);
+    }
+
+    pub fn add_retry(&self) {
+        self.update(|pending| pending.retry_count += 1);
+    }
+
+    pub fn add_hedged_request(&self) {
+        self.update(|pending| pending.hedged_requests += 1);
+    }
+
+    pub fn add_cache_lookup(&self, hit: bool) {
+        self.update(|pending| match hit {
+            true => pending.cache_hits += 1,
+            false => pending.cache_misses += 1,
+        });
+    }
+
+    pub fn add_stale_response(&self) {
+        self.update(|pending| pending.stale_responses += 1);
+    }
+
+    /// Update this updater's pending stats, and those of its parents.
+    fn update(&self, f: impl Fn(&mut PendingStats)) {
+        let mut updater = Some(self);
+        while let Some(u) = updater {
+            f(&mut u.pending.lock().unwrap());
+            updater = u.parent.as_deref();
+        }
+    }
+
+    /// Make this the current updater while `fut` runs.
+    pub async fn scope<F: Future>(self: Arc<Self>, fut: F) -> F::Output {
+        CURRENT.scope(self, fut).await
+    }
+
+    /// Make this the current updater while `f` runs, e.g. a call to a service,
+    /// which may already answer from a cache.
+    pub fn sync_scope<R>(self: Arc<Self>, f: impl FnOnce() -> R) -> R {
+        CURRENT.sync_scope(self, f)
+    }
+
+    /// Call `f` with the updater of the request being processed, if any.
+    pub fn with_current(f: impl FnOnce(&StatsUpdater)) {
+        let _ = CURRENT.try_with(|updater| f(updater));
     }
 }
 
 impl Drop for StatsUpdater {
     fn drop(&mut self) {
+        let pending = self.pending.get_mut().unwrap();
+        let elapsed_time = Instant::now().duration_since(self.request_start_time);
         let mut stats = self.stats.write().unwrap();
         stats.request_count += 1;
-        stats.elapsed_time += Instant::now().duration_since(self.request_start_time);
-        stats.rate_limited_time += self.rate_limited_time;
+        stats.elapsed_time += elapsed_time;
+        stats.rate_limited_time += pending.rate_limited_time;
+        stats.queued_time += pending.queued_time;
+        stats.retry_count += pending.retry_count;
+        stats.hedged_request_count += pending.hedged_requests;
+        if let Some((method, url)) = self.request.take() {
+            let method_stats = stats.methods.entry(method).or_default();
+            method_stats.record(elapsed_time, pending.outcome);
+            method_stats.cache_hits += pending.cache_hits;
+            method_stats.cache_misses += pending.cache_misses;
+            method_stats.stale_responses += pending.stale_responses;
+            if let Some(url) = url.filter(|_| pending.endpoints.is_empty()) {
+                pending.endpoints.push((url, elapsed_time, pending.outcome));
+            }
+        }
+        for (url, latency, outcome) in pending.endpoints.drain(..) {
+            stats
+                .endpoints
+                .entry(url)
+                .or_default()
+                .record(latency, outcome);
+        }
     }
 }
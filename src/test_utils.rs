@@ -0,0 +1,317 @@
+//! A scriptable local HTTP JSON-RPC server, for testing HTTP-level behavior of client stacks,
+//! e.g. how an [HttpClientBuilder](crate::service::builder::HttpClientBuilder) handles
+//! 429 responses, slow nodes, or malformed bodies. Enabled with the `test-utils` feature.
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use solana_rpc_tower::{prelude::*, service::builder::ServiceBuilderExt};
+//! # use solana_rpc_tower::test_utils::{MockResponse, TestServer};
+//! # async fn demo() {
+//! let server = TestServer::start();
+//! server.script(
+//!     RpcRequest::GetSlot,
+//!     [MockResponse::too_many_requests(Some("1")), MockResponse::result(42)],
+//! );
+//! let rpc_client = RpcClientBuilder::new().http(server.url()).build_rpc_client();
+//! assert_eq!(rpc_client.get_slot().await.unwrap(), 42);
+//! assert_eq!(server.calls(RpcRequest::GetSlot), 2);
+//! # }
+//! ```
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER},
+    service::{make_service_fn, service_fn},
+    Body, HeaderMap, Request, Response, Server, StatusCode,
+};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use tokio::sync::oneshot;
+
+use crate::service::mock::METHOD_NOT_FOUND;
+
+#[derive(Debug, Clone)]
+enum ResponseBody {
+    Result(Value),
+    Error { code: i64, message: String },
+    Raw(Vec<u8>),
+}
+
+/// What the [TestServer] answers a request with.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: ResponseBody,
+    delay: Duration,
+}
+
+impl MockResponse {
+    fn new(status: StatusCode, body: ResponseBody) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// A successful JSON-RPC response with this result.
+    pub fn result(result: impl Serialize) -> Self {
+        let result = serde_json::to_value(result).expect("result serializes to JSON");
+        Self::new(StatusCode::OK, ResponseBody::Result(result))
+    }
+
+    /// A JSON-RPC error response.
+    pub fn rpc_error(code: i64, message: &str) -> Self {
+        let body = ResponseBody::Error {
+            code,
+            message: message.to_string(),
+        };
+        Self::new(StatusCode::OK, body)
+    }
+
+    /// A response with this HTTP status and an empty body.
+    pub fn status(status: u16) -> Self {
+        let status = StatusCode::from_u16(status).expect("valid HTTP status");
+        Self::new(status, ResponseBody::Raw(Vec::new()))
+    }
+
+    /// HTTP 429, with a `Retry-After` header if given, e.g. `"2"` or an HTTP date.
+    pub fn too_many_requests(retry_after: Option<&str>) -> Self {
+        let response = Self::status(429);
+        match retry_after {
+            Some(retry_after) => response.header(RETRY_AFTER.as_str(), retry_after),
+            None => response,
+        }
+    }
+
+    /// A response with this body as is, e.g. to test malformed responses.
+    pub fn raw(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(StatusCode::OK, ResponseBody::Raw(body.into()))
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = StatusCode::from_u16(status).expect("valid HTTP status");
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::from_bytes(name.as_bytes()).expect("valid header name"),
+            HeaderValue::from_str(value).expect("valid header value"),
+        );
+        self
+    }
+
+    /// Wait this long before responding.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn into_response(mut self, id: Value) -> Response<Body> {
+        let body = std::mem::replace(&mut self.body, ResponseBody::Raw(Vec::new()));
+        let body = match body {
+            ResponseBody::Result(result) => {
+                json!({ "jsonrpc": "2.0", "result": result, "id": id }).to_string()
+            }
+            ResponseBody::Error { code, message } => json!({
+                "jsonrpc": "2.0",
+                "error": { "code": code, "message": message },
+                "id": id,
+            })
+            .to_string(),
+            ResponseBody::Raw(body) => return self.build(body),
+        };
+        let mut response = self.build(body.into_bytes());
+        response
+            .headers_mut()
+            .entry(CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/json"));
+        response
+    }
+
+    fn build(self, body: Vec<u8>) -> Response<Body> {
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+    }
+}
+
+/// A request the [TestServer] received.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    pub params: Value,
+    pub headers: HeaderMap,
+}
+
+type Handler = Arc<dyn Fn(&Value) -> MockResponse + Send + Sync>;
+
+#[derive(Default)]
+struct ServerState {
+    handlers: HashMap<String, Handler>,
+    scripts: HashMap<String, VecDeque<MockResponse>>,
+    received: Vec<ReceivedRequest>,
+}
+
+impl ServerState {
+    /// The next scripted response for `method`, or else its handler.
+    fn response(&mut self, method: &str) -> Result<MockResponse, Handler> {
+        if let Some(response) = self.scripts.get_mut(method).and_then(VecDeque::pop_front) {
+            return Ok(response);
+        }
+        match self.handlers.get(method) {
+            Some(handler) => Err(handler.clone()),
+            None => Ok(MockResponse::rpc_error(
+                METHOD_NOT_FOUND,
+                "Method not found",
+            )),
+        }
+    }
+}
+
+/// A local HTTP JSON-RPC server on its own thread, answering requests with the
+/// handlers and scripted responses configured per method. Methods without either
+/// get a "Method not found" JSON-RPC error. It can be reconfigured while running,
+/// and shuts down when dropped.
+pub struct TestServer {
+    url: Url,
+    state: Arc<Mutex<ServerState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestServer {
+    /// Start the server on a free local port.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(ServerState::default()));
+        let (addr_sender, addr_receiver) = std::sync::mpsc::channel();
+        let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("test server runtime");
+            runtime.block_on(async move {
+                let make_service = make_service_fn(move |_| {
+                    let state = server_state.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |request| {
+                            handle(state.clone(), request)
+                        }))
+                    }
+                });
+                let server =
+                    Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+                addr_sender.send(server.local_addr()).unwrap();
+                let shutdown = async {
+                    let _ = shutdown_receiver.await;
+                };
+                if let Err(e) = server.with_graceful_shutdown(shutdown).await {
+                    tracing::error!(err = %e, "test server failed");
+                }
+            });
+        });
+        let addr = addr_receiver.recv().expect("test server started");
+        Self {
+            url: format!("http://{addr}").parse().unwrap(),
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// Answer every `method` request with the response `handler` returns for its params.
+    pub fn handle(
+        &self,
+        method: RpcRequest,
+        handler: impl Fn(&Value) -> MockResponse + Send + Sync + 'static,
+    ) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        state.handlers.insert(method.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Answer every `method` request with `response`.
+    pub fn respond(&self, method: RpcRequest, response: MockResponse) -> &Self {
+        self.handle(method, move |_| response.clone())
+    }
+
+    /// Answer the next `method` requests with these responses, one per request, in order.
+    /// After that, requests go to the method's handler again.
+    pub fn script(
+        &self,
+        method: RpcRequest,
+        responses: impl IntoIterator<Item = MockResponse>,
+    ) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let script = state.scripts.entry(method.to_string()).or_default();
+        script.extend(responses);
+        self
+    }
+
+    /// How many `method` requests were received.
+    pub fn calls(&self, method: RpcRequest) -> usize {
+        let method = method.to_string();
+        let state = self.state.lock().unwrap();
+        state.received.iter().filter(|r| r.method == method).count()
+    }
+
+    /// All requests received so far, in order.
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.state.lock().unwrap().received.clone()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<ServerState>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let headers = request.headers().clone();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(_) => return Ok(MockResponse::status(400).build(Vec::new())),
+    };
+    let Ok(body) = serde_json::from_slice::<Value>(&body) else {
+        let response = MockResponse::rpc_error(-32700, "Parse error");
+        return Ok(response.into_response(Value::Null));
+    };
+    let method = body["method"].as_str().unwrap_or_default().to_string();
+    let params = body["params"].clone();
+    let response = {
+        let mut state = state.lock().unwrap();
+        let response = state.response(&method);
+        state.received.push(ReceivedRequest {
+            method,
+            params: params.clone(),
+            headers,
+        });
+        response
+    };
+    // Handlers are called unlocked, since they may use the server too
+    let response = response.unwrap_or_else(|handler| handler(&params));
+    tokio::time::sleep(response.delay).await;
+    Ok(response.into_response(body["id"].clone()))
+}
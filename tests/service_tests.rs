@@ -1,21 +1,40 @@
 use futures::future::BoxFuture;
 use serde_json::Value;
 use solana_client::client_error::{ClientError, ClientErrorKind};
-use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_rpc_tower::middleware::{
+    cache::{BoundedMap, CacheKey, ResponseCacheLayer},
+    render_prometheus, BodyLogging, CacheCapacity, CacheStatus, Eviction, Fault, Jitter, Latency,
+};
 use solana_rpc_tower::prelude::*;
+use solana_rpc_tower::service::{
+    errors::{http_status, is_transport_error, quorum_error, reqwest_error, rpc_error_code},
+    rpc_sender_impl::{http_service, reqwest_client},
+    stats_updater::TransportStats,
+    Endpoint, EndpointSlot, Health, HealthChecker, MatchRule, QuorumError, SlotAgreement,
+};
+use solana_rpc_tower::test_utils::{MockResponse, TestServer};
 
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_response::{Response, RpcResponseContext, RpcVersionInfo};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
 use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::transport::TransportError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::{str::FromStr, thread::JoinHandle};
-use tower::{BoxError, ServiceBuilder};
+use tower::retry::{budget::TpsBudget, Policy};
+use tower::{BoxError, Layer, Service, ServiceBuilder, ServiceExt};
 
 use crossbeam_channel::unbounded;
 use futures::future;
 use jsonrpc_core::{IoHandler, Params};
-use jsonrpc_http_server::{AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
+use jsonrpc_http_server::{
+    hyper, AccessControlAllowOrigin, DomainsValidation, RequestMiddleware, RequestMiddlewareAction,
+    ServerBuilder,
+};
 use reqwest::Url;
 use solana_client::rpc_response::RpcBlockhash;
 use tracing_subscriber::fmt::format::FmtSpan;
@@ -78,6 +97,38 @@ pub fn io_handler_v1() -> IoHandler {
 /// }
 /// ```
 pub fn spawn_test_server(io: IoHandler) -> (Url, JoinHandle<()>) {
+    spawn_test_server_with_middleware(io, |request| RequestMiddlewareAction::Proceed {
+        should_continue_on_invalid_cors: false,
+        request,
+    })
+}
+
+/// Responds with HTTP 429 to the first `num_429` requests.
+pub fn spawn_rate_limited_test_server(io: IoHandler, num_429: u64) -> (Url, JoinHandle<()>) {
+    let count = AtomicU64::new(0);
+    spawn_test_server_with_middleware(io, move |request| {
+        if count.fetch_add(1, Ordering::Relaxed) >= num_429 {
+            return RequestMiddlewareAction::Proceed {
+                should_continue_on_invalid_cors: false,
+                request,
+            };
+        }
+        let response = hyper::Response::builder()
+            .status(429)
+            .header("Retry-After", "0")
+            .body(hyper::Body::empty())
+            .unwrap();
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: false,
+            response: Box::pin(future::ok(response)),
+        }
+    })
+}
+
+fn spawn_test_server_with_middleware(
+    io: IoHandler,
+    middleware: impl RequestMiddleware,
+) -> (Url, JoinHandle<()>) {
     let host = "127.0.0.1:0";
     let (sender, receiver) = unbounded();
     let rpc_addr = host.parse().unwrap();
@@ -87,6 +138,7 @@ pub fn spawn_test_server(io: IoHandler) -> (Url, JoinHandle<()>) {
             .cors(DomainsValidation::AllowOnly(vec![
                 AccessControlAllowOrigin::Any,
             ]))
+            .request_middleware(middleware)
             .start_http(&rpc_addr)
             .expect("Unable to start RPC server");
         let rpc_addr = Url::from_str(&format!("http://{}", server.address().clone())).unwrap();
@@ -144,6 +196,7 @@ async fn respects_inner_service_readiness() {
 /// Just try throwing on various [RpcClientBuilder] layers, to make sure
 /// they can compose to create an HTTP RPC client.
 #[tokio::test]
+#[allow(clippy::redundant_async_block)]
 async fn service_builder_layers() {
     let (url, _) = spawn_test_server(io_handler_v1());
 
@@ -368,3 +421,1647 @@ async fn low_level_constructors() {
         .to_string()
     );
 }
+
+#[tokio::test]
+async fn failover_to_next_endpoint() {
+    let (url, _) = spawn_test_server(io_handler_v1());
+    // Nothing listens on this port, so requests fail at the transport level.
+    let unreachable = Url::parse("http://127.0.0.1:1").unwrap();
+
+    let served_by = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let served_by_clone = served_by.clone();
+    let rpc_client = RpcClientBuilder::new()
+        .failover(vec![unreachable.clone(), url.clone()])
+        .on_served(move |_, url| served_by_clone.lock().unwrap().push(url.clone()))
+        .build_rpc_client();
+    assert_eq!(rpc_client.url(), unreachable.to_string());
+
+    let balance = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+    assert_eq!(balance, 50);
+    assert_eq!(*served_by.lock().unwrap(), vec![url.clone()]);
+
+    // So are bodies that aren't JSON, e.g. a proxy's error page.
+    let proxy = TestServer::start();
+    proxy.respond(
+        RpcRequest::GetBalance,
+        MockResponse::raw("<html>502 Bad Gateway</html>"),
+    );
+    let rpc_client = RpcClientBuilder::new()
+        .failover(vec![proxy.url(), url])
+        .build_rpc_client();
+    let balance = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+    assert_eq!(balance, 50);
+    assert_eq!(proxy.calls(RpcRequest::GetBalance), 1);
+
+    // JSON-RPC errors about the request itself are not retried on other endpoints.
+    let (url, _) = spawn_test_server(io_handler_v1());
+    let rpc_client = RpcClientBuilder::new()
+        .failover(vec![url, unreachable])
+        .build_rpc_client();
+    let result = rpc_client.get_slot().await.unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        ClientError::from(TransportError::Custom(
+            "RPC response error -32601: Method not found; ".to_string()
+        ))
+        .to_string()
+    );
+}
+
+fn io_handler_at_slot(balance: u64, slot: u64) -> IoHandler {
+    let mut io = IoHandler::default();
+    io.add_method("getBalance", move |_params: Params| {
+        future::ok(
+            serde_json::to_value(Response {
+                context: RpcResponseContext {
+                    slot,
+                    api_version: None,
+                },
+                value: balance,
+            })
+            .unwrap(),
+        )
+    });
+    io.add_method("getSlot", move |_params: Params| future::ok(slot.into()));
+    io
+}
+
+#[tokio::test]
+async fn balance_over_endpoints() {
+    let (url_a, _) = spawn_test_server(io_handler_at_slot(1, 100));
+    let (url_b, _) = spawn_test_server(io_handler_at_slot(2, 100));
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let rpc_client = RpcClientBuilder::new()
+        .balance(vec![url_a.clone(), url_b.clone()])
+        .build_rpc_client();
+    let mut balances = vec![];
+    for _ in 0..4 {
+        balances.push(rpc_client.get_balance(&pubkey).await.unwrap());
+    }
+    assert_eq!(balances, vec![1, 2, 1, 2]);
+
+    let rpc_client = RpcClientBuilder::new()
+        .balance(vec![url_a.clone(), url_b.clone()])
+        .strategy(BalanceStrategy::Weighted(vec![2, 1]))
+        .build_rpc_client();
+    let mut balances = vec![];
+    for _ in 0..6 {
+        balances.push(rpc_client.get_balance(&pubkey).await.unwrap());
+    }
+    assert_eq!(balances, vec![1, 2, 1, 1, 2, 1]);
+
+    for strategy in [
+        BalanceStrategy::LeastOutstanding,
+        BalanceStrategy::peak_ewma(),
+    ] {
+        let rpc_client = RpcClientBuilder::new()
+            .balance(vec![url_a.clone(), url_b.clone()])
+            .strategy(strategy)
+            .build_rpc_client();
+        for _ in 0..4 {
+            let balance = rpc_client.get_balance(&pubkey).await.unwrap();
+            assert!(balance == 1 || balance == 2);
+        }
+    }
+}
+
+#[tokio::test]
+async fn route_by_method() {
+    let (url_a, _) = spawn_test_server(io_handler_at_slot(1, 100));
+    let (url_b, _) = spawn_test_server(io_handler_v1());
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let rpc_client = RpcClientBuilder::new()
+        .router(tower::service_fn(rpc_response_fn))
+        .route([RpcRequest::GetBalance], http_service(url_a, 0))
+        .route(
+            [RpcRequest::GetBalance, RpcRequest::GetLatestBlockhash],
+            http_service(url_b.clone(), 0),
+        )
+        .url(url_b.to_string())
+        .build_rpc_client();
+    assert_eq!(rpc_client.url(), url_b.to_string());
+
+    // The last route for a method wins.
+    let balance = rpc_client.get_balance(&pubkey).await.unwrap();
+    assert_eq!(balance, 50);
+    let (blockhash, _) = rpc_client
+        .get_latest_blockhash_with_commitment(Default::default())
+        .await
+        .unwrap();
+    assert_eq!(blockhash.to_string(), pubkey.to_string());
+    // Unrouted methods go to the default service.
+    let version = rpc_client.get_version().await.unwrap();
+    assert_eq!(version.solana_core, "1.18.21");
+}
+
+#[tokio::test]
+async fn route_to_up_to_date_endpoints() {
+    let (url_a, _) = spawn_test_server(io_handler_at_slot(1, 100));
+    let (url_b, _) = spawn_test_server(io_handler_at_slot(2, 200));
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let service = SlotAwareService::new(
+        vec![
+            Endpoint::new(url_a.clone(), http_service(url_a.clone(), 0)),
+            Endpoint::new(url_b.clone(), http_service(url_b.clone(), 0)),
+        ],
+        10,
+    );
+    let rpc_client =
+        RpcClientSender::new_with_service(url_a.to_string(), service.clone()).into_rpc_client(None);
+
+    // Both endpoints get requests until their slots are known.
+    let mut balances = vec![];
+    for _ in 0..4 {
+        balances.push(rpc_client.get_balance(&pubkey).await.unwrap());
+    }
+    assert_eq!(balances, vec![1, 2, 2, 2]);
+    assert_eq!(
+        service.endpoint_slots(),
+        vec![
+            EndpointSlot {
+                url: url_a.clone(),
+                slot: Some(100),
+                degraded: true,
+            },
+            EndpointSlot {
+                url: url_b.clone(),
+                slot: Some(200),
+                degraded: false,
+            },
+        ]
+    );
+
+    // Slots can also be polled.
+    let service = SlotAwareService::new(
+        vec![
+            Endpoint::new(url_a.clone(), http_service(url_a.clone(), 0)),
+            Endpoint::new(url_b.clone(), http_service(url_b, 0)),
+        ],
+        10,
+    );
+    service.refresh_slots().await;
+    let slots = service.endpoint_slots();
+    assert_eq!(slots[0].slot, Some(100));
+    assert!(slots[0].degraded);
+    assert_eq!(slots[1].slot, Some(200));
+}
+
+#[tokio::test]
+async fn hedge_slow_requests() {
+    // Fast on the first call, slow afterwards.
+    let calls = Arc::new(AtomicU64::new(0));
+    let mut slow_io = IoHandler::default();
+    slow_io.add_method("getBalance", move |_params: Params| {
+        if calls.fetch_add(1, Ordering::Relaxed) > 0 {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        future::ok(
+            serde_json::to_value(Response {
+                context: RpcResponseContext {
+                    slot: 100,
+                    api_version: None,
+                },
+                value: 1,
+            })
+            .unwrap(),
+        )
+    });
+    let (primary, _) = spawn_test_server(slow_io);
+    let (secondary, _) = spawn_test_server(io_handler_at_slot(2, 100));
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let hedge = HedgeService::new(
+        Endpoint::new(primary.clone(), http_service(primary, 0)),
+        Endpoint::new(secondary.clone(), http_service(secondary, 0)),
+    )
+    .min_samples(1);
+    let sender = RpcClientSender::new_with_service(String::new(), hedge);
+    let stats = sender.transport_stats();
+    let rpc_client = sender.into_rpc_client(None);
+
+    let balance = rpc_client.get_balance(&pubkey).await.unwrap();
+    assert_eq!(balance, 1);
+    assert_eq!(stats.read().unwrap().hedged_request_count, 0);
+
+    let before = Instant::now();
+    let balance = rpc_client.get_balance(&pubkey).await.unwrap();
+    assert_eq!(balance, 2);
+    assert!(before.elapsed() < Duration::from_millis(500));
+    let stats = stats.read().unwrap();
+    assert_eq!(stats.hedged_request_count, 1);
+    assert_eq!(stats.request_count, 2);
+}
+
+#[tokio::test]
+async fn quorum_reads() {
+    let (url_a, _) = spawn_test_server(io_handler_at_slot(1, 100));
+    let (url_b, _) = spawn_test_server(io_handler_at_slot(1, 101));
+    let (url_c, _) = spawn_test_server(io_handler_at_slot(2, 102));
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let rpc_client = RpcClientBuilder::new()
+        .quorum(vec![url_a.clone(), url_b.clone(), url_c.clone()], 2)
+        .build_rpc_client();
+    let response = rpc_client
+        .get_balance_with_commitment(&pubkey, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(response.value, 1);
+    assert_eq!(response.context.slot, 101);
+
+    // Agreeing on the value is not enough if the slots differ.
+    let rpc_client = RpcClientBuilder::new()
+        .quorum(vec![url_a.clone(), url_b.clone(), url_c.clone()], 2)
+        .require_same_slot(true)
+        .build_rpc_client();
+    let err = rpc_client.get_balance(&pubkey).await.unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("No quorum for getBalance: 1 of 2 required endpoints agreed"),
+        "{err}"
+    );
+    // The typed error can be recovered from the RpcClient's error
+    assert!(matches!(err.kind, ClientErrorKind::Custom(_)), "{err:?}");
+    let quorum_err = quorum_error(&err).unwrap();
+    assert_eq!(quorum_err.method, RpcRequest::GetBalance);
+    assert_eq!((quorum_err.agreed, quorum_err.quorum), (1, 2));
+    assert_eq!(quorum_err.responses.len(), 3);
+    let (url, response) = &quorum_err.responses[0];
+    assert!([&url_a, &url_b, &url_c].contains(&url));
+    assert!(response.as_ref().unwrap()["context"]["slot"].is_u64());
+    assert!(!is_transport_error(&BoxError::from(err)));
+
+    // Only results at the minimum slot or newer count
+    let rpc_client = RpcClientBuilder::new()
+        .quorum(vec![url_a.clone(), url_b.clone(), url_c.clone()], 2)
+        .slot_agreement(SlotAgreement::AtLeast(100))
+        .build_rpc_client();
+    assert_eq!(rpc_client.get_balance(&pubkey).await.unwrap(), 1);
+    let rpc_client = RpcClientBuilder::new()
+        .quorum(vec![url_a.clone(), url_b.clone(), url_c.clone()], 2)
+        .slot_agreement(SlotAgreement::AtLeast(101))
+        .build_rpc_client();
+    let err = rpc_client.get_balance(&pubkey).await.unwrap_err();
+    assert_eq!(quorum_error(&err).unwrap().agreed, 1);
+
+    let err = QuorumService::new(
+        vec![
+            Endpoint::new(url_a.clone(), http_service(url_a.clone(), 0)),
+            Endpoint::new(url_c.clone(), http_service(url_c.clone(), 0)),
+        ],
+        2,
+    )
+    .call((
+        RpcRequest::GetBalance,
+        serde_json::json!([pubkey.to_string()]),
+    ))
+    .await
+    .unwrap_err()
+    .downcast::<QuorumError>()
+    .unwrap();
+    assert_eq!(err.agreed, 1);
+    assert_eq!(err.responses.len(), 2);
+}
+
+#[tokio::test]
+async fn skip_unhealthy_endpoints() {
+    let mut unhealthy_io = io_handler_at_slot(1, 100);
+    unhealthy_io.add_method("getHealth", |_params: Params| {
+        future::err(jsonrpc_core::Error {
+            code: jsonrpc_core::ErrorCode::ServerError(-32005),
+            message: "Node is behind by 42 slots".to_string(),
+            data: Some(serde_json::json!({"numSlotsBehind": 42})),
+        })
+    });
+    let mut healthy_io = io_handler_at_slot(2, 142);
+    healthy_io.add_method("getHealth", |_params: Params| future::ok("ok".into()));
+    let (url_a, _) = spawn_test_server(unhealthy_io);
+    let (url_b, _) = spawn_test_server(healthy_io);
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let endpoints = vec![
+        Endpoint::new(url_a.clone(), http_service(url_a, 0)),
+        Endpoint::new(url_b.clone(), http_service(url_b, 0)),
+    ];
+    let checker = HealthChecker::new(endpoints.clone()).check_slot(true);
+    let rpc_client =
+        RpcClientSender::new_with_service(String::new(), FailoverService::new(endpoints.clone()))
+            .into_rpc_client(None);
+
+    // The primary is used until it is known to be unhealthy.
+    assert_eq!(rpc_client.get_balance(&pubkey).await.unwrap(), 1);
+    checker.check_all().await;
+    assert_eq!(
+        endpoints[0].health().health(),
+        Health::Unhealthy {
+            num_slots_behind: Some(42)
+        }
+    );
+    assert_eq!(endpoints[1].health().health(), Health::Healthy);
+    assert_eq!(endpoints[1].health().slot(), Some(142));
+    assert_eq!(rpc_client.get_balance(&pubkey).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn not_ready_while_no_endpoint_is_healthy() {
+    let healthy = Arc::new(AtomicBool::new(false));
+    let healthy_clone = healthy.clone();
+    let mock = MockService::new().try_respond(RpcRequest::GetHealth, move |_| match healthy_clone
+        .load(Ordering::Relaxed)
+    {
+        true => Ok("ok"),
+        false => Err(BoxError::from("Node is unhealthy")),
+    });
+    let endpoints = vec![
+        Endpoint::new("http://a".parse().unwrap(), mock.clone()),
+        Endpoint::new("http://b".parse().unwrap(), mock),
+    ];
+    let checker = HealthChecker::new(endpoints.clone()).backoff(Duration::ZERO, Duration::ZERO);
+    checker.check_all().await;
+    let ready = tokio::spawn(FailoverService::new(endpoints.clone()).ready_oneshot());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!ready.is_finished());
+
+    // Woken up once an endpoint is re-admitted
+    healthy.store(true, Ordering::Relaxed);
+    checker.check_all().await;
+    tokio::time::timeout(Duration::from_secs(1), ready)
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+
+    // Without a checker left to re-admit an endpoint, waiting fails instead of hanging
+    healthy.store(false, Ordering::Relaxed);
+    drop(checker);
+    let checker = HealthChecker::new(endpoints.clone())
+        .interval(Duration::from_millis(10))
+        .backoff(Duration::ZERO, Duration::ZERO)
+        .spawn();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let ready = tokio::spawn(FailoverService::new(endpoints.clone()).ready_oneshot());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!ready.is_finished());
+    checker.abort();
+    let err = tokio::time::timeout(Duration::from_secs(1), ready)
+        .await
+        .unwrap()
+        .unwrap()
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("No endpoint is healthy"), "{err}");
+    let err = FailoverService::new(endpoints).ready_oneshot().await.err();
+    assert!(err.is_some());
+}
+
+#[tokio::test]
+async fn circuit_breaker_opens_on_node_errors() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let unhealthy = Arc::new(AtomicBool::new(true));
+    let (calls_clone, unhealthy_clone) = (calls.clone(), unhealthy.clone());
+    let rpc_client = RpcClientBuilder::new()
+        .layer(CircuitBreakerLayer::new(
+            CircuitBreakerConfig::default()
+                .consecutive_failures(2)
+                .open_duration(Duration::from_millis(200)),
+        ))
+        .with_fn(move |(method, _params)| {
+            calls_clone.fetch_add(1, Ordering::Relaxed);
+            let unhealthy = unhealthy_clone.load(Ordering::Relaxed);
+            async move {
+                match method {
+                    RpcRequest::GetBalance if unhealthy => {
+                        Err(Box::new(RpcError::RpcResponseError {
+                            code: -32005,
+                            message: "Node is unhealthy".to_string(),
+                            data: RpcResponseErrorData::NodeUnhealthy {
+                                num_slots_behind: None,
+                            },
+                        }) as BoxError)
+                    }
+                    RpcRequest::GetBalance => Ok(serde_json::to_value(Response {
+                        context: RpcResponseContext {
+                            slot: 100,
+                            api_version: None,
+                        },
+                        value: 7,
+                    })
+                    .unwrap()),
+                    // A problem with the request, not the node.
+                    _ => Err(Box::new(RpcError::RpcResponseError {
+                        code: -32602,
+                        message: "Invalid params".to_string(),
+                        data: RpcResponseErrorData::Empty,
+                    }) as BoxError),
+                }
+            }
+        })
+        .build_rpc_client();
+    let pubkey = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    for _ in 0..3 {
+        rpc_client.get_slot().await.unwrap_err();
+    }
+    rpc_client.get_balance(&pubkey).await.unwrap_err();
+    rpc_client.get_balance(&pubkey).await.unwrap_err();
+    assert_eq!(calls.load(Ordering::Relaxed), 5);
+
+    // Open: fail fast without calling the inner service.
+    let err = rpc_client.get_balance(&pubkey).await.unwrap_err();
+    assert!(err.to_string().contains("Circuit breaker is open"), "{err}");
+    assert_eq!(calls.load(Ordering::Relaxed), 5);
+
+    // Half-open: a successful probe closes the circuit.
+    unhealthy.store(false, Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(rpc_client.get_balance(&pubkey).await.unwrap(), 7);
+    assert_eq!(rpc_client.get_balance(&pubkey).await.unwrap(), 7);
+    assert_eq!(calls.load(Ordering::Relaxed), 7);
+}
+
+#[tokio::test]
+async fn retry_rpc_errors() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let calls_clone = calls.clone();
+    let rpc_client = RpcClientBuilder::new()
+        .retry(
+            RpcErrorRetry::new(3)
+                .delay(Duration::ZERO)
+                .methods([RpcRequest::GetBalance]),
+        )
+        .with_fn(move |(method, _params)| {
+            // Fails twice before answering.
+            let n = calls_clone.fetch_add(1, Ordering::Relaxed);
+            async move {
+                if n % 3 < 2 {
+                    return Err(Box::new(RpcError::RpcResponseError {
+                        code: -32004,
+                        message: "Block not available".to_string(),
+                        data: RpcResponseErrorData::Empty,
+                    }) as BoxError);
+                }
+                match method {
+                    RpcRequest::GetBalance => Ok(serde_json::to_value(Response {
+                        context: RpcResponseContext {
+                            slot: 100,
+                            api_version: None,
+                        },
+                        value: 3,
+                    })
+                    .unwrap()),
+                    _ => Ok(Value::from(100)),
+                }
+            }
+        })
+        .build_rpc_client();
+
+    let balance = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+    assert_eq!(balance, 3);
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    // Methods outside the allowlist are not retried.
+    rpc_client.get_slot().await.unwrap_err();
+    assert_eq!(calls.load(Ordering::Relaxed), 4);
+
+    // The HTTP client can be retried as well.
+    let (url, _) = spawn_test_server(io_handler_v1());
+    let rpc_client = RpcClientBuilder::new()
+        .retry(RpcErrorRetry::new(3))
+        .http(url)
+        .build_rpc_client();
+    let balance = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+    assert_eq!(balance, 50);
+}
+
+fn too_many_requests(retry_after: Option<&str>) -> Result<reqwest::Response, reqwest::Error> {
+    let mut response = http::Response::builder().status(429);
+    if let Some(retry_after) = retry_after {
+        response = response.header("Retry-After", retry_after);
+    }
+    Ok(response.body("").unwrap().into())
+}
+
+#[tokio::test]
+async fn too_many_requests_backoff() {
+    let mut req = reqwest::Request::new(reqwest::Method::POST, "http://localhost".parse().unwrap());
+    let wait =
+        |sleep: tokio::time::Sleep| sleep.deadline().duration_since(tokio::time::Instant::now());
+
+    // By default, a fixed 500ms unless Retry-After asks for less than 2 minutes.
+    let mut policy = TooManyRequestsRetry::new(4);
+    for retry_after in [None, Some("120"), None] {
+        let waited = wait(
+            policy
+                .retry(&mut req, &mut too_many_requests(retry_after))
+                .unwrap(),
+        );
+        assert!(waited <= Duration::from_millis(500) && waited > Duration::from_millis(400));
+    }
+    let sleep = policy
+        .retry(&mut req, &mut too_many_requests(Some("5")))
+        .unwrap();
+    assert!(wait(sleep) > Duration::from_secs(4));
+
+    // Exponential backoff without jitter, capped at the max delay.
+    let mut policy = TooManyRequestsRetry::new(4)
+        .backoff(Duration::from_secs(1), Duration::from_secs(3))
+        .jitter(Jitter::None);
+    let waits: Vec<_> = (0..4)
+        .map(|_| {
+            wait(
+                policy
+                    .retry(&mut req, &mut too_many_requests(None))
+                    .unwrap(),
+            )
+        })
+        .collect();
+    assert!(waits[0] <= Duration::from_secs(1) && waits[0] > Duration::from_millis(900));
+    assert!(waits[1] <= Duration::from_secs(2) && waits[1] > Duration::from_millis(1900));
+    assert!(waits[2] <= Duration::from_secs(3) && waits[2] > Duration::from_millis(2900));
+    assert!(waits[3] <= Duration::from_secs(3) && waits[3] > Duration::from_millis(2900));
+    assert!(policy
+        .retry(&mut req, &mut too_many_requests(None))
+        .is_none());
+
+    // Clones, one per request, draw different jitter
+    let policy = TooManyRequestsRetry::new(4)
+        .backoff(Duration::from_secs(1), Duration::from_secs(3))
+        .jitter(Jitter::Full);
+    let waits: Vec<_> = (0..3)
+        .map(|_| {
+            wait(
+                policy
+                    .clone()
+                    .retry(&mut req, &mut too_many_requests(None))
+                    .unwrap(),
+            )
+        })
+        .collect();
+    let spread = waits
+        .iter()
+        .max()
+        .unwrap()
+        .saturating_sub(*waits.iter().min().unwrap());
+    assert!(spread > Duration::from_millis(1), "{waits:?}");
+
+    // Retry-After as an HTTP date, until the max total wait is reached.
+    let mut policy = TooManyRequestsRetry::new(4).max_total_wait(Duration::from_secs(15));
+    let date = httpdate::fmt_http_date(std::time::SystemTime::now() + Duration::from_secs(11));
+    let sleep = policy
+        .retry(&mut req, &mut too_many_requests(Some(&date)))
+        .unwrap();
+    assert!(wait(sleep) > Duration::from_secs(9));
+    assert!(policy
+        .retry(&mut req, &mut too_many_requests(Some("5")))
+        .is_none());
+    assert!(policy.rate_limited_time() <= Duration::from_secs(11));
+
+    // A shared budget stops retries across policies.
+    let budget = Arc::new(TpsBudget::new(Duration::from_secs(1), 1, 0.0));
+    let policy = TooManyRequestsRetry::new(4)
+        .backoff(Duration::ZERO, Duration::ZERO)
+        .budget(budget);
+    let retried = (0..4)
+        .filter(|_| {
+            policy
+                .clone()
+                .retry(&mut req, &mut too_many_requests(None))
+                .is_some()
+        })
+        .count();
+    assert_eq!(retried, 1);
+}
+
+#[tokio::test]
+async fn rate_limited_time_in_stats() {
+    let (url, _) = spawn_rate_limited_test_server(io_handler_v1(), 2);
+    let stats = Arc::new(std::sync::RwLock::new(TransportStats::default()));
+    let sender = RpcClientSender::new_with_stats(
+        url.to_string(),
+        RpcClientBuilder::new()
+            .retry(RpcErrorRetry::new(1))
+            .service(http_service(url, 4)),
+        stats.clone(),
+    );
+    let rpc_client = sender.into_rpc_client(None);
+    let balance = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+    assert_eq!(balance, 50);
+
+    let stats = stats.read().unwrap().clone();
+    assert_eq!(stats.request_count, 1);
+    assert_eq!(stats.retry_count, 2);
+    assert!(stats.rate_limited_time <= stats.elapsed_time);
+    assert!(stats.queued_time <= stats.elapsed_time);
+}
+
+#[tokio::test]
+async fn per_method_and_endpoint_stats() {
+    let (url, _) = spawn_test_server(io_handler_v1());
+    let unreachable = Url::parse("http://127.0.0.1:1").unwrap();
+    let endpoints = vec![
+        Endpoint::new(unreachable.clone(), http_service(unreachable.clone(), 0)),
+        Endpoint::new(url.clone(), http_service(url.clone(), 0)),
+    ];
+    let sender =
+        RpcClientSender::new_with_service("failover".to_string(), FailoverService::new(endpoints));
+    let stats = sender.transport_stats();
+    let rpc_client = sender.into_rpc_client(None);
+
+    for _ in 0..3 {
+        rpc_client
+            .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+            .await
+            .unwrap();
+    }
+    rpc_client.get_slot().await.unwrap_err();
+
+    let stats = stats.read().unwrap().clone();
+    assert_eq!(stats.request_count, 4);
+    let balance = &stats.methods[&RpcRequest::GetBalance];
+    assert_eq!(balance.request_count, 3);
+    assert_eq!(balance.error_count, 0);
+    assert_eq!(balance.latency.count(), 3);
+    assert!(balance.latency.p50() <= balance.latency.p99());
+    assert!(balance.latency.p99() > Duration::ZERO);
+    let slot = &stats.methods[&RpcRequest::GetSlot];
+    assert_eq!(slot.error_count, 1);
+    assert_eq!(slot.errors_by_code[&-32601], 1);
+
+    let unreachable = &stats.endpoints[unreachable.as_str()];
+    assert_eq!(unreachable.request_count, 4);
+    assert_eq!(unreachable.error_count, 4);
+    assert!(unreachable.errors_by_code.is_empty());
+    let reachable = &stats.endpoints[url.as_str()];
+    assert_eq!(reachable.request_count, 4);
+    assert_eq!(reachable.error_count, 1);
+    assert!(!stats.endpoints.contains_key("failover"));
+}
+
+#[tokio::test]
+async fn prometheus_metrics() {
+    let stats = Arc::new(std::sync::RwLock::new(TransportStats::default()));
+    let rpc_client = RpcClientBuilder::new()
+        .layer(MetricsLayer::new(stats.clone()).endpoint("http://rpc.test"))
+        .layer(ResponseCacheLayer::new(
+            RpcRequest::GetBalance,
+            Duration::from_secs(60),
+        ))
+        .with_fn(|(method, _params)| async move {
+            match method {
+                RpcRequest::GetBalance => Ok(serde_json::to_value(Response {
+                    context: RpcResponseContext {
+                        slot: 100,
+                        api_version: None,
+                    },
+                    value: 3,
+                })
+                .unwrap()),
+                _ => Err(Box::new(RpcError::RpcResponseError {
+                    code: -32004,
+                    message: "Block not available".to_string(),
+                    data: RpcResponseErrorData::Empty,
+                }) as BoxError),
+            }
+        })
+        .build_rpc_client();
+
+    for _ in 0..3 {
+        rpc_client
+            .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+            .await
+            .unwrap();
+    }
+    rpc_client.get_slot().await.unwrap_err();
+
+    let metrics = render_prometheus(&stats.read().unwrap());
+    for line in [
+        "# TYPE solana_rpc_requests_total counter",
+        "solana_rpc_requests_total 4",
+        "solana_rpc_method_requests_total{method=\"getBalance\"} 3",
+        "solana_rpc_method_requests_total{method=\"getSlot\"} 1",
+        "solana_rpc_method_errors_total{method=\"getSlot\",code=\"-32004\"} 1",
+        "solana_rpc_endpoint_requests_total{endpoint=\"http://rpc.test\"} 4",
+        "solana_rpc_endpoint_errors_total{endpoint=\"http://rpc.test\",code=\"-32004\"} 1",
+        "# TYPE solana_rpc_method_request_duration_seconds histogram",
+        "solana_rpc_method_request_duration_seconds_bucket{method=\"getBalance\",le=\"30\"} 3",
+        "solana_rpc_method_request_duration_seconds_bucket{method=\"getBalance\",le=\"+Inf\"} 3",
+        "solana_rpc_method_request_duration_seconds_count{method=\"getBalance\"} 3",
+        "solana_rpc_cache_hits_total{method=\"getBalance\"} 2",
+        "solana_rpc_cache_misses_total{method=\"getBalance\"} 1",
+    ] {
+        assert!(
+            metrics.lines().any(|l| l == line),
+            "missing {line:?} in:\n{metrics}"
+        );
+    }
+    assert!(!metrics.contains("solana_rpc_cache_hits_total{method=\"getSlot\"}"));
+}
+
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn trace_rpc_requests() {
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer({
+            let logs = logs.clone();
+            move || logs.clone()
+        })
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (url, _) = spawn_test_server(io_handler_v1());
+    let rpc_client = RpcClientBuilder::new()
+        .layer(TraceLayer::new().body_logging(BodyLogging::Truncated(12)))
+        .http(url.clone())
+        .build_rpc_client();
+    rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap();
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(output.contains(&format!(
+        "rpc_request{{method=getBalance id=0 endpoint={url} status=200 body_size="
+    )));
+    assert!(output.contains(r#"rpc response body="{\"context\":{...""#));
+    assert!(!output.contains("deadbeef"));
+
+    // Failed HTTP calls have a status too
+    let server = TestServer::start();
+    server.respond(RpcRequest::GetSlot, MockResponse::status(503));
+    let rpc_client = RpcClientBuilder::new()
+        .layer(TraceLayer::new())
+        .http(server.url())
+        .build_rpc_client();
+    rpc_client.get_slot().await.unwrap_err();
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(
+        output
+            .lines()
+            .any(|l| l.contains("rpc_request{method=getSlot") && l.contains(" status=503")),
+        "{output}"
+    );
+}
+
+#[tokio::test]
+async fn redact_secrets() {
+    let url = Url::parse("http://127.0.0.1:1/v2/secret-in-path?api-key=secret-in-query").unwrap();
+    let rpc_client = RpcClientBuilder::new()
+        .http(url.clone())
+        .redactor(Redactor::default().path_segment(1))
+        .build_rpc_client();
+    assert_eq!(
+        rpc_client.url(),
+        "http://127.0.0.1:1/v2/REDACTED?api-key=REDACTED"
+    );
+    let err = rpc_client.get_slot().await.unwrap_err().to_string();
+    assert!(err.contains("/v2/REDACTED?api-key=REDACTED"), "{err}");
+    assert!(!err.contains("secret"), "{err}");
+
+    // So are the errors of non-success statuses
+    let server = TestServer::start();
+    server.respond(RpcRequest::GetSlot, MockResponse::status(503));
+    let mut failing = server.url();
+    failing.set_query(Some("api-key=secret-in-query"));
+    let rpc_client = RpcClientBuilder::new().http(failing).build_rpc_client();
+    let err = rpc_client.get_slot().await.unwrap_err().to_string();
+    assert!(err.contains("503"), "{err}");
+    assert!(!err.contains("secret"), "{err}");
+
+    // And the headers sent with every request, when an endpoint echoes them
+    server.respond(
+        RpcRequest::GetSlot,
+        MockResponse::rpc_error(-32000, "Invalid API key secret-header-value"),
+    );
+    let rpc_client = RpcClientBuilder::new()
+        .http(server.url())
+        .header(
+            "x-api-key".parse().unwrap(),
+            "secret-header-value".parse().unwrap(),
+        )
+        .build_rpc_client();
+    let err = rpc_client.get_slot().await.unwrap_err().to_string();
+    assert!(err.contains("Invalid API key REDACTED"), "{err}");
+    assert!(!err.contains("secret"), "{err}");
+
+    // Error messages mentioning several endpoints are redacted too.
+    let other = Url::parse("http://127.0.0.1:2/?token=secret-token").unwrap();
+    let rpc_client = RpcClientBuilder::new()
+        .quorum(vec![url, other], 2)
+        .redactor(Redactor::default().path_segment(1))
+        .build_rpc_client();
+    let err = rpc_client
+        .get_balance(&pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("No quorum"), "{err}");
+    assert!(!err.contains("secret"), "{err}");
+
+    // Secrets are matched as they are encoded in URLs too, but not when they are too short
+    let redactor = Redactor::default().path_segment(0);
+    let url = Url::parse("http://rpc.test/path%20secret?api-key=query+secret/1&token=abc").unwrap();
+    let text = "GET http://rpc.test/path%20secret?api-key=query+secret%2F1, \
+        key path secret, key query secret/1, tabc";
+    let redacted = redactor.redact_text(text, &url, &Default::default());
+    assert_eq!(
+        redacted,
+        "GET http://rpc.test/REDACTED?api-key=REDACTED, key REDACTED, key REDACTED, tabc"
+    );
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("authorization", "Bearer secret".parse().unwrap());
+    headers.insert("x-other", "visible".parse().unwrap());
+    let headers = Redactor::default().redact_headers(&headers);
+    assert_eq!(headers["authorization"], "REDACTED");
+    assert_eq!(headers["x-other"], "visible");
+}
+
+#[tokio::test]
+async fn record_and_replay() {
+    let cassette = std::env::temp_dir().join(format!("cassette-{}.jsonl", std::process::id()));
+    let (url, _handle) = spawn_test_server(io_handler_v1());
+    let rpc_client = RpcClientBuilder::new()
+        .layer(RecordLayer::new(&cassette).unwrap())
+        .http(url)
+        .build_rpc_client();
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+    assert_eq!(rpc_client.get_balance(&key).await.unwrap(), 50);
+    let version = rpc_client.get_version().await.unwrap();
+    // Not served by the test server
+    let slot_err = rpc_client.get_slot().await.unwrap_err().to_string();
+
+    let rpc_client = RpcClientBuilder::new()
+        .replay(ReplayService::from_file(&cassette).unwrap())
+        .build_rpc_client();
+    assert_eq!(rpc_client.get_balance(&key).await.unwrap(), 50);
+    assert_eq!(rpc_client.get_version().await.unwrap(), version);
+    // Served again once used up
+    assert_eq!(rpc_client.get_version().await.unwrap(), version);
+    assert_eq!(
+        rpc_client.get_slot().await.unwrap_err().to_string(),
+        slot_err
+    );
+
+    // Unmatched requests fail loudly
+    let other = pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+    let err = rpc_client
+        .get_balance(&other)
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("no recorded response for getBalance"), "{err}");
+    assert!(err.contains(&other.to_string()), "{err}");
+
+    // Unless the matching rules say otherwise
+    let replay = ReplayService::from_file(&cassette).unwrap().match_rule_for(
+        RpcRequest::GetBalance,
+        MatchRule::IgnoreFields(vec!["/0".into()]),
+    );
+    let rpc_client = RpcClientBuilder::new().replay(replay).build_rpc_client();
+    assert_eq!(rpc_client.get_balance(&other).await.unwrap(), 50);
+    std::fs::remove_file(&cassette).unwrap();
+}
+
+#[tokio::test]
+async fn typed_mock_service() {
+    let mock = MockService::new()
+        .slot(100)
+        .respond(RpcRequest::GetBalance, |_params| 123456789u64)
+        .respond(RpcRequest::GetSlot, |_params| 42u64)
+        .error(RpcRequest::GetBlockHeight, -32005, "Node is behind")
+        .expect(RpcRequest::GetBalance, 2)
+        .expect(RpcRequest::GetSlot, 1);
+    let rpc_client = RpcClientBuilder::new()
+        .mock(mock.clone())
+        .build_rpc_client();
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let balance = rpc_client
+        .get_balance_with_commitment(&key, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(balance.context.slot, 100);
+    assert_eq!(balance.value, 123456789);
+    mock.set_slot(101);
+    let balance = rpc_client
+        .get_balance_with_commitment(&key, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(balance.context.slot, 101);
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 42);
+
+    let err = rpc_client.get_block_height().await.unwrap_err().to_string();
+    assert!(err.contains("Node is behind"), "{err}");
+    let err = rpc_client.get_version().await.unwrap_err().to_string();
+    assert!(
+        err.contains("-32601") && err.contains("Method not found"),
+        "{err}"
+    );
+    assert_eq!(mock.calls(RpcRequest::GetVersion), 1);
+    mock.assert_expectations();
+
+    let mock = mock.expect(RpcRequest::GetSlot, 2);
+    let unmet =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| mock.assert_expectations()))
+            .unwrap_err();
+    assert_eq!(
+        unmet.downcast_ref::<String>().unwrap(),
+        "unmet expectations: getSlot: expected 2 calls, got 1"
+    );
+}
+
+#[tokio::test]
+async fn fake_ledger() {
+    use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+    use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+    use solana_sdk::{
+        account::Account, signature::Keypair, signer::Signer, system_instruction,
+        transaction::Transaction,
+    };
+
+    let ledger = FakeLedger::new();
+    let rpc_client =
+        RpcClientSender::new_with_service(String::new(), ledger.clone()).into_rpc_client(None);
+
+    let payer = Keypair::new();
+    ledger.set_balance(payer.pubkey(), 1_000_000);
+    assert_eq!(
+        rpc_client.get_balance(&payer.pubkey()).await.unwrap(),
+        1_000_000
+    );
+    assert_eq!(
+        rpc_client.get_balance(&Pubkey::new_unique()).await.unwrap(),
+        0
+    );
+
+    let program = Pubkey::new_unique();
+    let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    for (pubkey, data) in [(a, vec![1, 2, 3]), (b, vec![1, 9, 3, 4])] {
+        let account = Account {
+            lamports: 10,
+            data,
+            owner: program,
+            ..Default::default()
+        };
+        ledger.set_account(pubkey, account);
+    }
+    let account = rpc_client.get_account(&a).await.unwrap();
+    assert_eq!(account.data, vec![1, 2, 3]);
+    assert_eq!(account.owner, program);
+    let accounts = rpc_client
+        .get_multiple_accounts(&[a, Pubkey::new_unique()])
+        .await
+        .unwrap();
+    assert_eq!(accounts[0].as_ref().unwrap().data, vec![1, 2, 3]);
+    assert!(accounts[1].is_none());
+
+    let program_accounts = |filters| {
+        rpc_client.get_program_accounts_with_config(
+            &program,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(solana_account_decoder::UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    };
+    let accounts = program_accounts(vec![RpcFilterType::DataSize(4)])
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].0, b);
+    let memcmp = RpcFilterType::Memcmp(Memcmp::new_raw_bytes(1, vec![2]));
+    let accounts = program_accounts(vec![memcmp]).await.unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].0, a);
+    assert_eq!(program_accounts(vec![]).await.unwrap().len(), 2);
+
+    let blockhash = rpc_client.get_latest_blockhash().await.unwrap();
+    assert_eq!(blockhash, ledger.latest_blockhash());
+    ledger.advance_slots(3);
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 4);
+    assert_ne!(rpc_client.get_latest_blockhash().await.unwrap(), blockhash);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(&payer.pubkey(), &a, 100)],
+        Some(&payer.pubkey()),
+        &[&payer],
+        blockhash,
+    );
+    let signature = rpc_client.send_transaction(&transaction).await.unwrap();
+    assert_eq!(signature, transaction.signatures[0]);
+    assert_eq!(ledger.transactions().len(), 1);
+    assert_eq!(ledger.transactions()[0].signatures[0], signature);
+    let statuses = rpc_client
+        .get_signature_statuses(&[signature])
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(statuses[0].as_ref().unwrap().slot, 4);
+
+    let err = rpc_client.get_version().await.unwrap_err().to_string();
+    assert!(err.contains("Method not found"), "{err}");
+}
+
+async fn injected_error(fault: Fault) -> BoxError {
+    let mock = MockService::new().respond(RpcRequest::GetSlot, |_| 100u64);
+    FaultInjectionLayer::new(1)
+        .fault(fault, 1.0)
+        .layer(mock)
+        .oneshot((RpcRequest::GetSlot, Value::Null))
+        .await
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn fault_injection() {
+    // Above an RPC service
+    let mock = MockService::new()
+        .slot(100)
+        .respond(RpcRequest::GetBalance, |_| 50u64)
+        .respond(RpcRequest::GetSlot, |_| 100u64);
+    let mut service = FaultInjectionLayer::new(1)
+        .latency_for(
+            [RpcRequest::GetSlot],
+            Latency::Fixed(Duration::from_millis(50)),
+        )
+        .fault_for([RpcRequest::GetBalance], Fault::StaleSlot(10), 1.0)
+        .layer(mock);
+    let start = Instant::now();
+    let slot = service
+        .call((RpcRequest::GetSlot, Value::Null))
+        .await
+        .unwrap();
+    assert_eq!(slot, 100);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    let balance = service
+        .call((RpcRequest::GetBalance, Value::Null))
+        .await
+        .unwrap();
+    assert_eq!(balance["context"]["slot"], 90);
+    assert_eq!(balance["value"], 50);
+
+    assert!(is_transport_error(
+        &injected_error(Fault::TransportError).await
+    ));
+    let err = injected_error(Fault::too_many_requests(None)).await;
+    assert_eq!(
+        http_status(&err),
+        Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+    );
+    let fault = Fault::RpcError {
+        code: -32005,
+        message: "Node is behind".to_string(),
+    };
+    assert_eq!(rpc_error_code(&injected_error(fault).await), Some(-32005));
+    assert!(injected_error(Fault::TruncatedBody)
+        .await
+        .is::<serde_json::Error>());
+    assert!(injected_error(Fault::MalformedBody)
+        .await
+        .is::<serde_json::Error>());
+
+    // The same seed injects the same faults
+    let failures = |seed| async move {
+        let mock = MockService::new().respond(RpcRequest::GetSlot, |_| 100u64);
+        let mut service = FaultInjectionLayer::new(seed)
+            .fault(Fault::TransportError, 0.5)
+            .layer(mock);
+        let mut failures = Vec::new();
+        for _ in 0..32 {
+            let result = service.call((RpcRequest::GetSlot, Value::Null)).await;
+            failures.push(result.is_err());
+        }
+        failures
+    };
+    let first = failures(42).await;
+    assert_eq!(first, failures(42).await);
+    assert_ne!(first, failures(43).await);
+    assert!(first.contains(&true) && first.contains(&false));
+
+    // Between the JSON-RPC and HTTP layers
+    let (url, _handle) = spawn_test_server(io_handler_v1());
+    let http = |faults: FaultInjectionLayer| {
+        ServiceBuilder::new()
+            .layer(ParseResponseBodyLayer)
+            .layer(HttpRequestLayer::new(url.clone()))
+            .retry(TooManyRequestsRetry::new(3))
+            .layer(faults)
+            .service(reqwest_client())
+    };
+    let get_balance = || {
+        (
+            RpcRequest::GetBalance,
+            serde_json::json!([Pubkey::new_unique().to_string()]),
+        )
+    };
+
+    let mut service = http(FaultInjectionLayer::new(1).fault(Fault::StaleSlot(10), 1.0));
+    let balance = service.call(get_balance()).await.unwrap();
+    assert_eq!(balance["context"]["slot"], 90);
+
+    let mut service = http(FaultInjectionLayer::new(1).fault(Fault::TransportError, 1.0));
+    let err = service.call(get_balance()).await.unwrap_err();
+    assert!(reqwest_error(&err).unwrap().is_timeout());
+
+    let mut service = http(
+        FaultInjectionLayer::new(1).fault(Fault::service_unavailable(Some(Duration::ZERO)), 1.0),
+    );
+    let err = service.call(get_balance()).await.unwrap_err();
+    assert_eq!(
+        http_status(&err),
+        Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+    );
+
+    let fault = Fault::RpcError {
+        code: -32005,
+        message: "Node is behind".to_string(),
+    };
+    let mut service = http(FaultInjectionLayer::new(1).fault(fault, 1.0));
+    let err = service.call(get_balance()).await.unwrap_err();
+    assert_eq!(rpc_error_code(&err), Some(-32005));
+
+    // 429s with `Retry-After` reach the retry policy
+    let mut service = http(
+        FaultInjectionLayer::new(1).fault(Fault::too_many_requests(Some(Duration::ZERO)), 0.5),
+    );
+    let start = Instant::now();
+    for _ in 0..4 {
+        service.call(get_balance()).await.unwrap();
+    }
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn scriptable_test_server() {
+    let server = TestServer::start();
+    server
+        .script(
+            RpcRequest::GetSlot,
+            [
+                MockResponse::too_many_requests(Some("0")),
+                MockResponse::result(42),
+            ],
+        )
+        .respond(RpcRequest::GetSlot, MockResponse::result(43))
+        .respond(
+            RpcRequest::GetBlockHeight,
+            MockResponse::result(7).delay(Duration::from_millis(50)),
+        )
+        .handle(RpcRequest::GetBalance, |params| match params[0].as_str() {
+            Some("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh") => {
+                MockResponse::result(serde_json::json!({ "context": { "slot": 1 }, "value": 50 }))
+            }
+            _ => MockResponse::rpc_error(-32602, "Invalid params"),
+        });
+    let rpc_client = RpcClientBuilder::new()
+        .http(server.url())
+        .build_rpc_client();
+
+    // The 429 is retried
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 42);
+    assert_eq!(server.calls(RpcRequest::GetSlot), 2);
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 43);
+
+    let start = Instant::now();
+    assert_eq!(rpc_client.get_block_height().await.unwrap(), 7);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+    assert_eq!(rpc_client.get_balance(&key).await.unwrap(), 50);
+    let err = rpc_client
+        .get_balance(&Pubkey::new_unique())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid params"), "{err}");
+
+    server.script(RpcRequest::GetSlot, [MockResponse::status(503)]);
+    let err = rpc_client.get_slot().await.unwrap_err();
+    assert!(err.to_string().contains("503"), "{err}");
+    server.script(RpcRequest::GetSlot, [MockResponse::raw("<html>")]);
+    assert!(rpc_client.get_slot().await.is_err());
+
+    let err = rpc_client.get_version().await.unwrap_err().to_string();
+    assert!(err.contains("Method not found"), "{err}");
+
+    // Handlers can use the server, e.g. to answer by how often they were called
+    let server = Arc::new(server);
+    let handled = Arc::downgrade(&server);
+    server.handle(RpcRequest::GetBlockHeight, move |_| {
+        let server = handled.upgrade().unwrap();
+        MockResponse::result(server.calls(RpcRequest::GetBlockHeight))
+    });
+    assert_eq!(rpc_client.get_block_height().await.unwrap(), 2);
+
+    let received = server.received();
+    assert_eq!(received[0].method, "getSlot");
+    assert!(received[0].headers.contains_key("solana-client"));
+}
+
+#[tokio::test]
+async fn multi_method_cache() {
+    let mock = MockService::new()
+        .slot(100)
+        .respond(RpcRequest::GetBalance, |_| 50u64)
+        .respond(RpcRequest::GetSlot, |params| {
+            match params.to_string().contains("processed") {
+                true => 110u64,
+                false => 101u64,
+            }
+        })
+        .respond(RpcRequest::GetLatestBlockhash, |_| RpcBlockhash {
+            blockhash: "deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh".to_string(),
+            last_valid_block_height: 100,
+        });
+    let cache = ResponseCacheLayer::default()
+        .policy(
+            RpcRequest::GetBalance,
+            CachePolicy::Ttl(Duration::from_millis(100)),
+        )
+        .policy(
+            RpcRequest::GetLatestBlockhash,
+            CachePolicy::UntilSlotAdvances(Duration::from_secs(10)),
+        )
+        .policy(RpcRequest::GetSlot, CachePolicy::Never);
+    let rpc_client = RpcClientBuilder::new()
+        .layer(cache.clone())
+        .mock(mock.clone())
+        .build_rpc_client();
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    rpc_client.get_balance(&key).await.unwrap();
+    rpc_client.get_balance(&key).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 1);
+    // Different params are cached separately
+    rpc_client.get_balance(&Pubkey::new_unique()).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 2);
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    rpc_client.get_balance(&key).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 3);
+
+    rpc_client.get_latest_blockhash().await.unwrap();
+    rpc_client.get_latest_blockhash().await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetLatestBlockhash), 1);
+    // The slot moves on
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 101);
+    assert_eq!(rpc_client.get_slot().await.unwrap(), 101);
+    assert_eq!(mock.calls(RpcRequest::GetSlot), 2);
+    rpc_client.get_latest_blockhash().await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetLatestBlockhash), 2);
+    // Slots are tracked by commitment, and processed is expected to be ahead of finalized
+    let processed = rpc_client
+        .get_slot_with_commitment(CommitmentConfig::processed())
+        .await
+        .unwrap();
+    assert_eq!(processed, 110);
+    rpc_client.get_latest_blockhash().await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetLatestBlockhash), 2);
+
+    let counters = cache.counters();
+    assert_eq!(counters[&RpcRequest::GetBalance].hits, 1);
+    assert_eq!(counters[&RpcRequest::GetBalance].misses, 3);
+    assert_eq!(counters[&RpcRequest::GetLatestBlockhash].hits, 2);
+    assert_eq!(counters[&RpcRequest::GetLatestBlockhash].misses, 2);
+    assert!(!counters.contains_key(&RpcRequest::GetSlot));
+}
+
+#[tokio::test]
+async fn bounded_cache_eviction() {
+    let mock = MockService::new().respond(RpcRequest::GetBalance, |_| 50u64);
+    let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let get_balance = |service: &solana_rpc_tower::middleware::cache::ResponseCacheService<_>,
+                       key: &Pubkey| {
+        service
+            .clone()
+            .oneshot((RpcRequest::GetBalance, serde_json::json!([key.to_string()])))
+    };
+
+    // LRU: the least recently used response makes room
+    let lru = ResponseCacheLayer::new(RpcRequest::GetBalance, Duration::from_secs(60))
+        .capacity(CacheCapacity::default().max_entries(2))
+        .layer(mock.clone());
+    get_balance(&lru, &keys[0]).await.unwrap();
+    get_balance(&lru, &keys[1]).await.unwrap();
+    get_balance(&lru, &keys[0]).await.unwrap();
+    get_balance(&lru, &keys[2]).await.unwrap();
+    assert_eq!(lru.cached_responses(), 2);
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 3);
+    get_balance(&lru, &keys[0]).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 3);
+    get_balance(&lru, &keys[1]).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 4);
+
+    // TinyLFU: a scan over keys looked up once doesn't push out a hot key
+    let lfu = ResponseCacheLayer::new(RpcRequest::GetBalance, Duration::from_secs(60))
+        .capacity(
+            CacheCapacity::default()
+                .max_entries(1)
+                .eviction(Eviction::TinyLfu),
+        )
+        .layer(mock.clone());
+    for _ in 0..3 {
+        get_balance(&lfu, &keys[0]).await.unwrap();
+    }
+    let calls = mock.calls(RpcRequest::GetBalance);
+    for key in &keys[1..] {
+        get_balance(&lfu, key).await.unwrap();
+    }
+    get_balance(&lfu, &keys[0]).await.unwrap();
+    assert_eq!(mock.calls(RpcRequest::GetBalance), calls + 3);
+
+    // Replacing a cached value doesn't have to be admitted again
+    let mut map = BoundedMap::new(
+        CacheCapacity::default()
+            .max_bytes(10)
+            .eviction(Eviction::TinyLfu),
+    );
+    let expires_at = Instant::now() + Duration::from_secs(60);
+    assert!(map.insert("hot", 1, 5, expires_at));
+    for _ in 0..3 {
+        map.get(&"hot");
+    }
+    assert!(map.insert("cold", 1, 5, expires_at));
+    assert!(map.insert("cold", 2, 6, expires_at));
+    assert_eq!(map.peek(&"cold"), Some(&2));
+
+    // Responses that don't fit in the byte limit aren't cached
+    let tiny = ResponseCacheLayer::new(RpcRequest::GetBalance, Duration::from_secs(60))
+        .capacity(CacheCapacity::default().max_bytes(16))
+        .layer(mock.clone());
+    get_balance(&tiny, &keys[0]).await.unwrap();
+    assert_eq!(tiny.cached_responses(), 0);
+
+    // Expired responses are purged
+    let cache = ResponseCacheLayer::default()
+        .policy(
+            RpcRequest::GetBalance,
+            CachePolicy::Ttl(Duration::from_millis(50)),
+        )
+        .capacity(CacheCapacity::default().max_bytes(1024));
+    let service = cache.layer(mock.clone());
+    for key in &keys {
+        service
+            .clone()
+            .oneshot((RpcRequest::GetBalance, serde_json::json!([key.to_string()])))
+            .await
+            .unwrap();
+    }
+    assert_eq!(cache.cached_responses(), 4);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(cache.purge_expired(), 4);
+    assert_eq!(cache.cached_responses(), 0);
+}
+
+#[tokio::test]
+async fn coalesce_in_flight_requests() {
+    let calls = Arc::new(AtomicU64::new(0));
+    let counted = calls.clone();
+    let coalesce = CoalesceLayer::new();
+    let cache = ResponseCacheLayer::default().policy(
+        RpcRequest::GetBalance,
+        CachePolicy::Ttl(Duration::from_secs(60)),
+    );
+    let rpc_client = RpcClientBuilder::new()
+        .layer(cache.clone())
+        .layer(coalesce.clone())
+        .with_fn(move |(method, _params)| {
+            let calls = counted.clone();
+            async move {
+                calls.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                assert_eq!(method, RpcRequest::GetBalance);
+                Ok(serde_json::json!({ "context": { "slot": 100 }, "value": 50 }))
+            }
+        })
+        .build_rpc_client();
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+
+    let balances = future::join_all((0..10).map(|_| rpc_client.get_balance(&key))).await;
+    assert!(balances.into_iter().all(|b| b.unwrap() == 50));
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert_eq!(coalesce.coalesced(), 9);
+    // Later requests are answered by the cache
+    assert_eq!(rpc_client.get_balance(&key).await.unwrap(), 50);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    // Errors are given to all waiters, and the next request is sent again
+    let counted = calls.clone();
+    let service = coalesce.layer(tower::service_fn(move |_req: SolanaClientRequest| {
+        let calls = counted.clone();
+        async move {
+            calls.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err::<Value, BoxError>(
+                RpcError::RpcResponseError {
+                    code: -32004,
+                    message: "Block not available".to_string(),
+                    data: RpcResponseErrorData::Empty,
+                }
+                .into(),
+            )
+        }
+    }));
+    let errors = future::join_all((0..3).map(|_| {
+        service
+            .clone()
+            .oneshot((RpcRequest::GetBlock, serde_json::json!([5])))
+    }))
+    .await;
+    for err in errors {
+        assert_eq!(rpc_error_code(&err.unwrap_err()), Some(-32004));
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    assert_eq!(coalesce.coalesced(), 11);
+    let _ = service
+        .oneshot((RpcRequest::GetBlock, serde_json::json!([5])))
+        .await;
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    // Methods with side effects are sent every time
+    let counted = calls.clone();
+    let service =
+        CoalesceLayer::new().layer(tower::service_fn(move |_req: SolanaClientRequest| {
+            let calls = counted.clone();
+            async move {
+                calls.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, BoxError>(Value::Null)
+            }
+        }));
+    future::join_all((0..3).map(|_| {
+        service
+            .clone()
+            .oneshot((RpcRequest::RequestAirdrop, serde_json::json!([5])))
+    }))
+    .await;
+    assert_eq!(calls.load(Ordering::Relaxed), 6);
+}
+
+#[tokio::test]
+async fn stale_cache_responses() {
+    let stats = Arc::new(std::sync::RwLock::new(TransportStats::default()));
+    let balance = Arc::new(AtomicU64::new(1));
+    let failing = Arc::new(AtomicBool::new(false));
+    let (current, fail) = (balance.clone(), failing.clone());
+    let rpc_client = RpcClientBuilder::new()
+        .layer(MetricsLayer::new(stats.clone()))
+        .layer(
+            ResponseCacheLayer::new(RpcRequest::GetBalance, Duration::from_millis(50))
+                .stale_while_revalidate(Duration::from_millis(100))
+                .stale_if_error(Duration::from_secs(60)),
+        )
+        .with_fn(move |_req| {
+            let (balance, failing) = (
+                current.load(Ordering::Relaxed),
+                fail.load(Ordering::Relaxed),
+            );
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                match failing {
+                    true => Err(BoxError::from(TransportError::Custom("down".to_string()))),
+                    false => Ok(serde_json::json!({ "context": { "slot": 1 }, "value": balance })),
+                }
+            }
+        })
+        .build_rpc_client();
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh");
+    let stale_responses = || stats.read().unwrap().methods[&RpcRequest::GetBalance].stale_responses;
+
+    let get_balance = || CacheStatus::scope(rpc_client.get_balance(&key));
+
+    let (balance_1, status) = get_balance().await;
+    assert_eq!(balance_1.unwrap(), 1);
+    assert_eq!(status, None);
+    balance.store(2, Ordering::Relaxed);
+    // Expired, so the stale balance is served and refreshed in the background
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    let (balance_1, status) = get_balance().await;
+    assert_eq!(balance_1.unwrap(), 1);
+    let status = status.unwrap();
+    assert!(status.stale);
+    assert!(status.age >= Duration::from_millis(60));
+    assert_eq!(stale_responses(), 1);
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    let (balance_2, status) = get_balance().await;
+    assert_eq!(balance_2.unwrap(), 2);
+    let status = status.unwrap();
+    assert!(!status.stale);
+    assert!(status.age < Duration::from_millis(50));
+    assert_eq!(stale_responses(), 1);
+
+    // Past the stale-while-revalidate window, the last good balance is served on errors
+    failing.store(true, Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (balance_2, status) = get_balance().await;
+    assert_eq!(balance_2.unwrap(), 2);
+    let status = status.unwrap();
+    assert!(status.stale);
+    assert!(status.age >= Duration::from_millis(200));
+    assert_eq!(stale_responses(), 2);
+    assert!(render_prometheus(&stats.read().unwrap())
+        .lines()
+        .any(|l| l == "solana_rpc_cache_stale_total{method=\"getBalance\"} 2"));
+}
+
+#[tokio::test]
+async fn commitment_aware_cache_keys() {
+    let mock = MockService::new()
+        .slot(10)
+        .respond(RpcRequest::GetBalance, |_| 50u64);
+    let cache = ResponseCacheLayer::new(RpcRequest::GetBalance, Duration::from_secs(60))
+        .default_commitment(CommitmentConfig::confirmed())
+        .max_cache_age_for(CommitmentLevel::Processed, Duration::from_millis(20))
+        .invalidate_on_newer_slot(true)
+        .layer(mock.clone());
+    let key = pubkey!("deadbeefXjn8o3yroDHxUtKsZZgoy4GPkPPXfouKNHh").to_string();
+    let get_balance = |params: Value| {
+        let cache = cache.clone();
+        async move {
+            cache
+                .oneshot((RpcRequest::GetBalance, params))
+                .await
+                .unwrap()
+        }
+    };
+
+    // Spelling out the default commitment is the same request
+    get_balance(serde_json::json!([key])).await;
+    get_balance(serde_json::json!([key, { "commitment": "confirmed" }])).await;
+    get_balance(serde_json::json!([key, {}])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 1);
+    assert_eq!(
+        CacheKey::new(
+            &serde_json::json!([key, { "commitment": "confirmed", "minContextSlot": 5 }]),
+            CommitmentLevel::Confirmed,
+        ),
+        CacheKey {
+            params: serde_json::json!([key]),
+            commitment: CommitmentLevel::Confirmed,
+            min_context_slot: Some(5),
+        }
+    );
+
+    // Processed responses are cached for less time
+    let processed = serde_json::json!([key, { "commitment": "processed" }]);
+    get_balance(processed.clone()).await;
+    get_balance(processed.clone()).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 2);
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    // A newer processed response doesn't drop the confirmed one, which is expected to be behind
+    mock.set_slot(12);
+    get_balance(processed).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 3);
+    get_balance(serde_json::json!([key])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 3);
+    // But a newer finalized response drops it
+    get_balance(serde_json::json!([key, { "commitment": "finalized" }])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 4);
+    get_balance(serde_json::json!([key])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 5);
+    get_balance(serde_json::json!([key])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 5);
+
+    // Cached responses older than minContextSlot aren't served
+    get_balance(serde_json::json!([key, { "minContextSlot": 12 }])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 5);
+    get_balance(serde_json::json!([key, { "minContextSlot": 13 }])).await;
+    assert_eq!(mock.calls(RpcRequest::GetBalance), 6);
+}